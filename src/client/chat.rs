@@ -1,25 +1,84 @@
+use std::time::Duration;
+
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
-use tracing::{debug, info};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use super::error::{LlmError, StructuredAttempt};
+use super::{LlmClient, UnsupportedParamPolicy};
 
-use super::LlmClient;
-use super::error::LlmError;
+use crate::convert::schema::{SchemaDialect, normalize_schema};
 use crate::convert::{thinking::build_thinking_params, to_openai};
-use crate::types::anthropic::{Message, MessagesRequest, MessagesResponse};
-use crate::types::common::{Provider, ResponseFormat, ThinkingConfig, ToolDefinition};
+use crate::types::anthropic::{
+    ContentBlock, Message, MessagesRequest, MessagesResponse, RequestMetadata,
+};
+use crate::types::common::{
+    CustomTool, Provider, ResponseFormat, ThinkingConfig, ToolChoice, ToolDefinition, Usage,
+};
+use crate::types::conversation::Conversation;
 use crate::types::openai::{self, ChatRequest};
 
 /// Options for a chat request.
-#[derive(Default)]
+///
+/// `model`, `max_tokens` and `timeout` override the [`ClientConfig`] values
+/// for this request only.
+///
+/// Parameters the target provider does not support are handled according to
+/// [`ClientConfig::unsupported_params`].
+///
+/// [`ClientConfig`]: super::ClientConfig
+/// [`ClientConfig::unsupported_params`]: super::ClientConfig::unsupported_params
+#[derive(Debug, Clone, Copy, Default)]
 pub struct ChatOptions<'a> {
+    pub model: Option<&'a str>,
+    pub max_tokens: Option<u32>,
+    pub timeout: Option<Duration>,
     pub system: Option<&'a str>,
     pub tools: Option<&'a [ToolDefinition]>,
     pub thinking: Option<&'a ThinkingConfig>,
     pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    /// Top-k sampling. Anthropic only.
+    pub top_k: Option<u32>,
+    pub stop_sequences: Option<&'a [String]>,
+    /// Sampling seed. OpenAI-compatible only.
+    pub seed: Option<u64>,
+    /// OpenAI-compatible only.
+    pub frequency_penalty: Option<f32>,
+    /// OpenAI-compatible only.
+    pub presence_penalty: Option<f32>,
+    /// End-user identifier (OpenAI `user`, Anthropic `metadata.user_id`).
+    pub user: Option<&'a str>,
     pub response_format: Option<&'a ResponseFormat>,
-    /// Require one named tool. The transport maps this to the provider's
+    /// Tool selection policy. The transport maps this to the provider's
     /// native tool-choice wire format.
+    pub tool_choice: Option<&'a ToolChoice>,
+    /// Require one named tool. Ignored when `tool_choice` is set.
+    #[deprecated(note = "use `tool_choice: Some(&ToolChoice::tool(name))`")]
     pub required_tool: Option<&'a str>,
+    /// Allow at most one tool call per response (Anthropic
+    /// `disable_parallel_tool_use`, OpenAI `parallel_tool_calls: false`).
+    pub disable_parallel_tool_use: bool,
+    /// Follow-up turns `complete_structured` may spend asking the model to
+    /// fix output that failed schema validation or deserialization. Ignored by other calls,
+    /// including structured streaming.
+    pub structured_repairs: u32,
+}
+
+impl ChatOptions<'_> {
+    /// `tool_choice`, falling back to the deprecated `required_tool`.
+    fn resolved_tool_choice(&self) -> Option<ToolChoice> {
+        #[allow(deprecated)]
+        self.tool_choice
+            .cloned()
+            .or_else(|| self.required_tool.map(ToolChoice::tool))
+    }
+
+    /// Whether the request offers any tools.
+    fn has_tools(&self) -> bool {
+        self.tools.is_some_and(|tools| !tools.is_empty())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,13 +100,14 @@ impl LlmClient {
         info!(
             "Sending request to LLM (provider: {}, model: {}, messages: {})",
             self.config.provider,
-            self.config.model,
+            self.model(&options),
             messages.len()
         );
 
+        let messages = self.fit_context(messages, &options).await?;
         match self.config.provider {
-            Provider::Anthropic => self.chat_anthropic(messages, &options).await,
-            Provider::OpenAiCompatible => self.chat_openai_compat(messages, &options).await,
+            Provider::Anthropic => self.chat_anthropic(&messages, &options).await,
+            Provider::OpenAiCompatible => self.chat_openai_compat(&messages, &options).await,
         }
     }
 
@@ -64,6 +124,30 @@ impl LlmClient {
         self.chat(&messages, options).await
     }
 
+    /// Send `conversation` and append the response to it as an assistant
+    /// turn. The conversation's system prompt and tools apply unless
+    /// `options` sets its own.
+    pub async fn chat_conversation(
+        &self,
+        conversation: &mut Conversation,
+        options: ChatOptions<'_>,
+    ) -> Result<MessagesResponse, LlmError> {
+        conversation.validate().map_err(LlmError::Config)?;
+        let tools = (!conversation.tools.is_empty()).then_some(conversation.tools.as_slice());
+        let response = self
+            .chat(
+                conversation.messages(),
+                ChatOptions {
+                    system: options.system.or(conversation.system.as_deref()),
+                    tools: options.tools.or(tools),
+                    ..options
+                },
+            )
+            .await?;
+        conversation.push_response(&response);
+        Ok(response)
+    }
+
     /// Complete a request with a strict JSON Schema response and deserialize it.
     ///
     /// OpenAI-compatible providers use `response_format.json_schema`. Native
@@ -77,89 +161,63 @@ impl LlmClient {
     where
         T: DeserializeOwned + JsonSchema,
     {
-        if schema_name.is_empty()
-            || schema_name.len() > 64
-            || !schema_name.chars().all(|character| {
-                character.is_ascii_alphanumeric() || matches!(character, '_' | '-')
-            })
-        {
-            return Err(LlmError::Config(
-                "schema name must contain 1 to 64 ASCII letters, digits, underscores, or hyphens"
-                    .into(),
-            ));
-        }
+        self.complete_structured_with(
+            user,
+            schema_name,
+            ChatOptions {
+                system,
+                ..ChatOptions::default()
+            },
+        )
+        .await
+    }
 
-        let schema = serde_json::to_value(schemars::schema_for!(T))
-            .map_err(|error| LlmError::Config(error.to_string()))?;
-        let response = match self.config.provider {
-            Provider::OpenAiCompatible => {
-                let response_format = ResponseFormat::json_schema(schema_name, schema, true);
-                self.complete(
-                    user,
-                    ChatOptions {
-                        system,
-                        temperature: Some(0.0),
-                        response_format: Some(&response_format),
-                        ..ChatOptions::default()
-                    },
-                )
-                .await?
-            }
-            Provider::Anthropic => {
-                let tools = [ToolDefinition::new(
-                    schema_name,
-                    "Submit the structured result using exactly this schema.",
-                    schema,
-                )];
-                self.complete(
-                    user,
-                    ChatOptions {
-                        system,
-                        temperature: Some(0.0),
-                        tools: Some(&tools),
-                        required_tool: Some(schema_name),
-                        ..ChatOptions::default()
-                    },
-                )
-                .await?
+    /// Like [`complete_structured`](Self::complete_structured), with full
+    /// request options (model, max_tokens, timeout, system, ...).
+    ///
+    /// The schema plumbing replaces `response_format`, `tools` and
+    /// `tool_choice`; `temperature` defaults to `0.0` unless `thinking` is
+    /// set, which Anthropic only accepts at its default. Output is checked
+    /// against the JSON Schema before it is deserialized. With
+    /// `structured_repairs` set, output that fails either check is sent back
+    /// with the error (a failing `tool_result` on Anthropic, a user message on
+    /// OpenAI) and usage is summed across attempts; if every attempt fails,
+    /// [`LlmError::StructuredRepairFailed`] lists them all.
+    pub async fn complete_structured_with<T>(
+        &self,
+        user: &str,
+        schema_name: &str,
+        options: ChatOptions<'_>,
+    ) -> Result<StructuredResponse<T>, LlmError>
+    where
+        T: DeserializeOwned + JsonSchema,
+    {
+        let provider = self.config.provider;
+        let plumbing = StructuredPlumbing::new::<T>(provider, schema_name)?;
+        let mut messages = vec![Message::user_text(user)];
+        let mut usage = Usage::default();
+        let mut attempts = Vec::new();
+        loop {
+            let response = self.chat(&messages, plumbing.options(options)).await?;
+            if let Some(response_usage) = &response.usage {
+                usage += response_usage;
             }
-        };
-        let data = match self.config.provider {
-            Provider::OpenAiCompatible => {
-                let text = response.text();
-                if text.trim().is_empty() {
-                    return Err(LlmError::EmptyResponse);
+            let (error, body) = match plumbing.parse(&response) {
+                Ok(data) => return Ok(StructuredResponse { data, usage }),
+                Err(LlmError::InvalidStructuredOutput { error, body }) => (error, body),
+                Err(error) => return Err(error),
+            };
+            if attempts.len() >= options.structured_repairs as usize {
+                if attempts.is_empty() {
+                    return Err(LlmError::InvalidStructuredOutput { error, body });
                 }
-                serde_json::from_str(&text).map_err(|error| LlmError::InvalidStructuredOutput {
-                    error: error.to_string(),
-                    body: text.chars().take(4_096).collect(),
-                })?
-            }
-            Provider::Anthropic => {
-                let input = response.content.iter().find_map(|block| match block {
-                    crate::types::anthropic::ContentBlock::ToolUse { name, input, .. }
-                        if name == schema_name =>
-                    {
-                        Some(input.clone())
-                    }
-                    _ => None,
-                });
-                let input = input.ok_or_else(|| LlmError::InvalidStructuredOutput {
-                    error: format!("provider did not call required tool {schema_name}"),
-                    body: response.text().chars().take(4_096).collect(),
-                })?;
-                serde_json::from_value(input.clone()).map_err(|error| {
-                    LlmError::InvalidStructuredOutput {
-                        error: error.to_string(),
-                        body: input.to_string().chars().take(4_096).collect(),
-                    }
-                })?
+                attempts.push(StructuredAttempt { error, body });
+                return Err(LlmError::StructuredRepairFailed { attempts, usage });
             }
-        };
-        Ok(StructuredResponse {
-            data,
-            usage: response.usage.unwrap_or_default(),
-        })
+            tracing::warn!(%error, attempt = attempts.len() + 1, "asking model to repair structured output");
+            messages.extend(repair_turn(provider, response, schema_name, &error));
+            attempts.push(StructuredAttempt { error, body });
+        }
     }
 
     /// Send a raw OpenAI-format chat request.
@@ -172,7 +230,7 @@ impl LlmClient {
         let url = self.endpoint("chat/completions");
         debug!("POST {url} (model: {})", request.model);
 
-        let body = self.send_json(&url, request).await?;
+        let body = self.send_json(&url, request, None).await?;
         let resp: openai::ChatResponse = serde_json::from_slice(&body)
             .map_err(|error| LlmError::ParseResponse(error.to_string()))?;
 
@@ -186,31 +244,12 @@ impl LlmClient {
         messages: &[Message],
         options: &ChatOptions<'_>,
     ) -> Result<MessagesResponse, LlmError> {
-        if options.response_format.is_some() {
-            return Err(LlmError::Config(
-                "response_format is not supported by the native Anthropic Messages transport"
-                    .into(),
-            ));
-        }
-        let (thinking, output_config) = build_thinking_params(options.thinking);
-
-        let request_body = MessagesRequest {
-            model: self.config.model.clone(),
-            max_tokens: self.config.max_tokens,
-            system: options.system.map(|s| s.to_string()),
-            messages: messages.to_vec(),
-            tools: options.tools.map(|t| t.to_vec()),
-            thinking,
-            output_config,
-            tool_choice: options
-                .required_tool
-                .map(|name| serde_json::json!({"type": "tool", "name": name})),
-        };
+        let request_body = self.anthropic_request(messages, options)?;
 
         let url = self.endpoint("v1/messages");
-        debug!("POST {url} (model: {})", self.config.model);
+        debug!("POST {url} (model: {})", request_body.model);
 
-        let body = self.send_json(&url, &request_body).await?;
+        let body = self.send_json(&url, &request_body, options.timeout).await?;
         let resp: MessagesResponse = serde_json::from_slice(&body)
             .map_err(|error| LlmError::ParseResponse(error.to_string()))?;
 
@@ -227,26 +266,12 @@ impl LlmClient {
         messages: &[Message],
         options: &ChatOptions<'_>,
     ) -> Result<MessagesResponse, LlmError> {
-        let openai_messages = to_openai::messages_to_openai(options.system, messages);
-
-        let tools = options.tools.map(to_openai::tools_to_openai);
-
-        let request_body = openai::ChatRequest {
-            model: self.config.model.clone(),
-            max_tokens: Some(self.config.max_tokens),
-            messages: openai_messages,
-            temperature: options.temperature,
-            tools,
-            response_format: options.response_format.cloned(),
-            tool_choice: options
-                .required_tool
-                .map(|name| serde_json::json!({"type": "function", "function": {"name": name}})),
-        };
+        let request_body = self.openai_request(messages, options)?;
 
         let url = self.endpoint("chat/completions");
-        debug!("POST {url} (model: {})", self.config.model);
+        debug!("POST {url} (model: {})", request_body.model);
 
-        let body = self.send_json(&url, &request_body).await?;
+        let body = self.send_json(&url, &request_body, options.timeout).await?;
         let openai_resp: openai::ChatResponse = serde_json::from_slice(&body)
             .map_err(|error| LlmError::ParseResponse(error.to_string()))?;
 
@@ -259,4 +284,666 @@ impl LlmClient {
         );
         Ok(resp)
     }
+
+    /// Build a native Anthropic Messages request. Shared with streaming.
+    pub(crate) fn anthropic_request(
+        &self,
+        messages: &[Message],
+        options: &ChatOptions<'_>,
+    ) -> Result<MessagesRequest, LlmError> {
+        if options.response_format.is_some() {
+            return Err(LlmError::Config(
+                "response_format is not supported by the native Anthropic Messages transport"
+                    .into(),
+            ));
+        }
+        self.check_unsupported(&[
+            ("seed", options.seed.is_some()),
+            ("frequency_penalty", options.frequency_penalty.is_some()),
+            ("presence_penalty", options.presence_penalty.is_some()),
+        ])?;
+        let (thinking, output_config) = build_thinking_params(options.thinking);
+        let has_tools = options.has_tools();
+        let disable_parallel_tool_use = options.disable_parallel_tool_use && has_tools;
+
+        Ok(MessagesRequest {
+            model: self.model(options).to_string(),
+            max_tokens: options.max_tokens.unwrap_or(self.config.max_tokens),
+            system: options.system.map(|s| s.to_string()),
+            messages: messages.to_vec(),
+            temperature: options.temperature,
+            top_p: options.top_p,
+            top_k: options.top_k,
+            stop_sequences: options.stop_sequences.map(<[_]>::to_vec),
+            metadata: options.user.map(|user| RequestMetadata {
+                user_id: user.to_string(),
+            }),
+            tools: options.tools.map(normalize_tools).transpose()?,
+            thinking,
+            output_config,
+            // Anthropic rejects `tool_choice` on requests without tools.
+            tool_choice: match options.resolved_tool_choice() {
+                _ if !has_tools => None,
+                Some(choice) => Some(choice.to_anthropic(disable_parallel_tool_use)),
+                None if disable_parallel_tool_use => Some(ToolChoice::Auto.to_anthropic(true)),
+                None => None,
+            },
+        })
+    }
+
+    /// Build an OpenAI-compatible chat request. Shared with streaming.
+    pub(crate) fn openai_request(
+        &self,
+        messages: &[Message],
+        options: &ChatOptions<'_>,
+    ) -> Result<ChatRequest, LlmError> {
+        if options
+            .tools
+            .is_some_and(|tools| tools.iter().any(ToolDefinition::is_server))
+        {
+            return Err(LlmError::Config(
+                "server tools are only supported by the native Anthropic Messages transport".into(),
+            ));
+        }
+        self.check_unsupported(&[("top_k", options.top_k.is_some())])?;
+
+        Ok(ChatRequest {
+            model: self.model(options).to_string(),
+            max_tokens: Some(options.max_tokens.unwrap_or(self.config.max_tokens)),
+            messages: to_openai::messages_to_openai(options.system, messages),
+            temperature: options.temperature,
+            top_p: options.top_p,
+            stop: options.stop_sequences.map(<[_]>::to_vec),
+            seed: options.seed,
+            frequency_penalty: options.frequency_penalty,
+            presence_penalty: options.presence_penalty,
+            user: options.user.map(str::to_string),
+            tools: options
+                .tools
+                .map(normalize_tools)
+                .transpose()?
+                .as_deref()
+                .map(to_openai::tools_to_openai),
+            response_format: options
+                .response_format
+                .map(normalize_response_format)
+                .transpose()?,
+            tool_choice: options
+                .resolved_tool_choice()
+                .as_ref()
+                .map(ToolChoice::to_openai),
+            // OpenAI rejects `parallel_tool_calls` on requests without tools.
+            parallel_tool_calls: (options.disable_parallel_tool_use && options.has_tools())
+                .then_some(false),
+            reasoning_effort: None,
+        })
+    }
+
+    /// Model for this request: the per-request override or the configured model.
+    pub(crate) fn model<'a>(&'a self, options: &ChatOptions<'a>) -> &'a str {
+        options.model.unwrap_or(&self.config.model)
+    }
+
+    /// Apply the configured [`UnsupportedParamPolicy`] to `(name, is_set)` pairs.
+    fn check_unsupported(&self, params: &[(&str, bool)]) -> Result<(), LlmError> {
+        for (name, _) in params.iter().filter(|(_, is_set)| *is_set) {
+            match self.config.unsupported_params {
+                UnsupportedParamPolicy::Drop => warn!(
+                    "Dropping {name}: not supported by the {} transport",
+                    self.config.provider
+                ),
+                UnsupportedParamPolicy::Error => {
+                    return Err(LlmError::Config(format!(
+                        "{name} is not supported by the {} transport",
+                        self.config.provider
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inline `$ref`s in custom tool input schemas where possible; recursive
+/// schemas are sent with their `$defs`.
+fn normalize_tools(tools: &[ToolDefinition]) -> Result<Vec<ToolDefinition>, LlmError> {
+    tools
+        .iter()
+        .map(|tool| match tool {
+            ToolDefinition::Custom(tool) => {
+                let input_schema =
+                    normalized(&tool.name, &tool.input_schema, SchemaDialect::ToolInput)?;
+                Ok(ToolDefinition::Custom(CustomTool {
+                    input_schema,
+                    ..tool.clone()
+                }))
+            }
+            ToolDefinition::Server(_) => Ok(tool.clone()),
+        })
+        .collect()
+}
+
+/// Rewrite a strict JSON Schema response format into the subset OpenAI
+/// strict mode accepts.
+fn normalize_response_format(format: &ResponseFormat) -> Result<ResponseFormat, LlmError> {
+    match format {
+        ResponseFormat::JsonSchema { json_schema } if json_schema.strict => {
+            Ok(ResponseFormat::json_schema(
+                json_schema.name.clone(),
+                normalized(
+                    &json_schema.name,
+                    &json_schema.schema,
+                    SchemaDialect::OpenAiStrict,
+                )?,
+                true,
+            ))
+        }
+        _ => Ok(format.clone()),
+    }
+}
+
+fn normalized(name: &str, schema: &Value, dialect: SchemaDialect) -> Result<Value, LlmError> {
+    let normalized = normalize_schema(schema, dialect)
+        .map_err(|error| LlmError::Config(format!("schema {name}: {error}")))?;
+    for change in &normalized.lossy {
+        warn!("Schema {name} relaxed for {dialect:?}: {change}");
+    }
+    Ok(normalized.schema)
+}
+
+/// Request settings that make a provider answer with JSON matching `T`:
+/// strict `response_format` on OpenAI-compatible providers, a forced tool on
+/// Anthropic.
+pub(crate) struct StructuredPlumbing {
+    provider: Provider,
+    schema_name: String,
+    validator: jsonschema::Validator,
+    response_format: Option<ResponseFormat>,
+    tools: Vec<ToolDefinition>,
+    tool_choice: Option<ToolChoice>,
+}
+
+impl StructuredPlumbing {
+    pub(crate) fn new<T: JsonSchema>(
+        provider: Provider,
+        schema_name: &str,
+    ) -> Result<Self, LlmError> {
+        if schema_name.is_empty()
+            || schema_name.len() > 64
+            || !schema_name.chars().all(|character| {
+                character.is_ascii_alphanumeric() || matches!(character, '_' | '-')
+            })
+        {
+            return Err(LlmError::Config(
+                "schema name must contain 1 to 64 ASCII letters, digits, underscores, or hyphens"
+                    .into(),
+            ));
+        }
+
+        let schema = serde_json::to_value(schemars::schema_for!(T))
+            .map_err(|error| LlmError::Config(error.to_string()))?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|error| LlmError::Config(format!("schema {schema_name}: {error}")))?;
+        let (response_format, tools, tool_choice) = match provider {
+            Provider::OpenAiCompatible => (
+                Some(ResponseFormat::json_schema(schema_name, schema, true)),
+                Vec::new(),
+                None,
+            ),
+            Provider::Anthropic => (
+                None,
+                vec![ToolDefinition::new(
+                    schema_name,
+                    "Submit the structured result using exactly this schema.",
+                    schema,
+                )],
+                Some(ToolChoice::tool(schema_name)),
+            ),
+        };
+        Ok(Self {
+            provider,
+            schema_name: schema_name.to_string(),
+            validator,
+            response_format,
+            tools,
+            tool_choice,
+        })
+    }
+
+    pub(crate) fn options<'a>(&'a self, options: ChatOptions<'a>) -> ChatOptions<'a> {
+        let tools = (!self.tools.is_empty()).then_some(self.tools.as_slice());
+        ChatOptions {
+            temperature: options
+                .temperature
+                .or(options.thinking.is_none().then_some(0.0)),
+            response_format: self.response_format.as_ref(),
+            tools,
+            tool_choice: self.tool_choice.as_ref(),
+            ..options
+        }
+    }
+
+    /// Extract the structured result from `response`, check it against the
+    /// JSON Schema and deserialize it.
+    pub(crate) fn parse<T: DeserializeOwned>(
+        &self,
+        response: &MessagesResponse,
+    ) -> Result<T, LlmError> {
+        let invalid = |error: String, body: &str| LlmError::InvalidStructuredOutput {
+            error,
+            body: body.chars().take(4_096).collect(),
+        };
+        let value = match self.provider {
+            Provider::OpenAiCompatible => {
+                let text = response.text();
+                if text.trim().is_empty() {
+                    return Err(LlmError::EmptyResponse);
+                }
+                serde_json::from_str::<Value>(&text)
+                    .map_err(|error| invalid(error.to_string(), &text))?
+            }
+            Provider::Anthropic => response
+                .content
+                .iter()
+                .find_map(|block| match block {
+                    ContentBlock::ToolUse { name, input, .. } if *name == self.schema_name => {
+                        Some(input.clone())
+                    }
+                    _ => None,
+                })
+                .ok_or_else(|| {
+                    invalid(
+                        format!("provider did not call required tool {}", self.schema_name),
+                        &response.text(),
+                    )
+                })?,
+        };
+        let violations: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .map(|error| match error.instance_path().as_str() {
+                "" => error.to_string(),
+                path => format!("{path}: {error}"),
+            })
+            .collect();
+        if !violations.is_empty() {
+            return Err(invalid(violations.join("; "), &value.to_string()));
+        }
+        serde_json::from_value(value.clone())
+            .map_err(|error| invalid(error.to_string(), &value.to_string()))
+    }
+}
+
+/// Messages that replay an invalid structured answer and ask for a fix: a
+/// failing `tool_result` on Anthropic, a user message on OpenAI.
+fn repair_turn(
+    provider: Provider,
+    response: MessagesResponse,
+    schema_name: &str,
+    error: &str,
+) -> Vec<Message> {
+    let feedback = format!(
+        "The output did not match the {schema_name} schema: {error}. Try again with corrected output."
+    );
+    let tool_use_id = response.content.iter().find_map(|block| match block {
+        ContentBlock::ToolUse { id, name, .. } if name == schema_name => Some(id.clone()),
+        _ => None,
+    });
+    let reply = match (provider, tool_use_id) {
+        (Provider::Anthropic, Some(id)) => {
+            Message::tool_results(vec![ContentBlock::tool_result(id, feedback, true)])
+        }
+        _ => Message::user_text(feedback),
+    };
+    vec![Message::assistant(response.content), reply]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientConfig;
+    use crate::client::test_server::{Reply, json_response, recording_server};
+
+    #[test]
+    fn maps_sampling_controls_and_applies_unsupported_policy() {
+        let stop = vec!["END".to_string()];
+        let options = ChatOptions {
+            top_p: Some(0.9),
+            top_k: Some(40),
+            seed: Some(7),
+            stop_sequences: Some(&stop),
+            user: Some("user-1"),
+            ..ChatOptions::default()
+        };
+        let overridden = ChatOptions {
+            model: Some("claude-small"),
+            max_tokens: Some(256),
+            ..ChatOptions::default()
+        };
+        let messages = [Message::user_text("hi")];
+
+        let anthropic = LlmClient::new(ClientConfig::anthropic("key", "claude")).expect("client");
+        let request = serde_json::to_value(
+            anthropic
+                .anthropic_request(
+                    &messages,
+                    &ChatOptions {
+                        model: overridden.model,
+                        max_tokens: overridden.max_tokens,
+                        ..options
+                    },
+                )
+                .expect("seed is dropped by default"),
+        )
+        .expect("serialize");
+        assert_eq!(request["top_k"], 40);
+        assert_eq!(request["model"], "claude-small");
+        assert_eq!(request["max_tokens"], 256);
+        assert_eq!(request["stop_sequences"], serde_json::json!(["END"]));
+        assert_eq!(request["metadata"]["user_id"], "user-1");
+        assert!(request.get("seed").is_none());
+
+        let openai = LlmClient::new(
+            ClientConfig::openai("key", "gpt").unsupported_params(UnsupportedParamPolicy::Error),
+        )
+        .expect("client");
+        assert!(matches!(
+            openai.openai_request(&messages, &options),
+            Err(LlmError::Config(_))
+        ));
+        let request = serde_json::to_value(
+            openai
+                .openai_request(
+                    &messages,
+                    &ChatOptions {
+                        top_k: None,
+                        ..options
+                    },
+                )
+                .expect("supported parameters"),
+        )
+        .expect("serialize");
+        assert_eq!(request["seed"], 7);
+        assert_eq!(request["model"], "gpt");
+        assert_eq!(request["stop"], serde_json::json!(["END"]));
+        assert_eq!(request["user"], "user-1");
+    }
+
+    #[tokio::test]
+    async fn applies_per_request_overrides() {
+        let (url, mut requests) = recording_server(vec![
+            Reply::Stall(String::new()),
+            Reply::Respond(json_response(
+                r#"{"id":"c","model":"gpt-small","choices":[{"index":0,"message":{"role":"assistant","content":"hi"},"finish_reason":"stop"}]}"#,
+            )),
+        ])
+        .await;
+        let client = LlmClient::new(ClientConfig::openai_compatible(url, "", "gpt")).unwrap();
+        let messages = [Message::user_text("hi")];
+
+        let error = client
+            .chat(
+                &messages,
+                ChatOptions {
+                    timeout: Some(Duration::from_millis(50)),
+                    ..ChatOptions::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, LlmError::Request(error) if error.is_timeout()),
+            "{error}"
+        );
+
+        client
+            .chat(
+                &messages,
+                ChatOptions {
+                    model: Some("gpt-small"),
+                    max_tokens: Some(256),
+                    ..ChatOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+        let timed_out = requests.recv().await.unwrap().body;
+        assert_eq!(timed_out["model"], "gpt");
+        let overridden = requests.recv().await.unwrap().body;
+        assert_eq!(overridden["model"], "gpt-small");
+        assert_eq!(overridden["max_tokens"], 256);
+    }
+
+    #[test]
+    fn sends_parallel_tool_settings_only_with_tools() {
+        let messages = [Message::user_text("hi")];
+        let tools = [ToolDefinition::new(
+            "search",
+            "",
+            serde_json::json!({ "type": "object" }),
+        )];
+        let single = ChatOptions {
+            disable_parallel_tool_use: true,
+            ..ChatOptions::default()
+        };
+
+        let anthropic = LlmClient::new(ClientConfig::anthropic("key", "claude")).expect("client");
+        let request = anthropic.anthropic_request(&messages, &single).unwrap();
+        assert_eq!(request.tool_choice, None);
+        let choice = ToolChoice::Any;
+        let request = anthropic
+            .anthropic_request(
+                &messages,
+                &ChatOptions {
+                    tool_choice: Some(&choice),
+                    ..single
+                },
+            )
+            .unwrap();
+        assert_eq!(request.tool_choice, None);
+        let request = anthropic
+            .anthropic_request(
+                &messages,
+                &ChatOptions {
+                    tools: Some(&[]),
+                    ..single
+                },
+            )
+            .unwrap();
+        assert_eq!(request.tool_choice, None);
+        let request = anthropic
+            .anthropic_request(
+                &messages,
+                &ChatOptions {
+                    tools: Some(&tools),
+                    ..single
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            request.tool_choice,
+            Some(serde_json::json!({ "type": "auto", "disable_parallel_tool_use": true }))
+        );
+
+        #[allow(deprecated)]
+        let required = ChatOptions {
+            tools: Some(&tools),
+            required_tool: Some("search"),
+            ..ChatOptions::default()
+        };
+        let request = anthropic.anthropic_request(&messages, &required).unwrap();
+        assert_eq!(
+            request.tool_choice,
+            Some(serde_json::json!({ "type": "tool", "name": "search" }))
+        );
+
+        let openai = LlmClient::new(ClientConfig::openai("key", "gpt")).expect("client");
+        let request = openai.openai_request(&messages, &single).unwrap();
+        assert_eq!(request.parallel_tool_calls, None);
+        let request = openai.openai_request(&messages, &required).unwrap();
+        assert_eq!(
+            request.tool_choice,
+            Some(serde_json::json!({ "type": "function", "function": { "name": "search" } }))
+        );
+    }
+
+    #[tokio::test]
+    async fn repairs_invalid_structured_output_with_tool_result_feedback() {
+        #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+        struct Person {
+            name: String,
+            #[schemars(range(min = 1))]
+            age: u32,
+        }
+
+        let reply = |input: &str| {
+            let body = format!(
+                r#"{{"id":"msg_1","type":"message","role":"assistant","model":"model","content":[{{"type":"tool_use","id":"toolu_1","name":"person","input":{input}}}],"stop_reason":"tool_use","usage":{{"input_tokens":10,"output_tokens":5}}}}"#
+            );
+            json_response(&body)
+        };
+        let responses = vec![
+            reply(r#"{"name":"Ada"}"#),
+            reply(r#"{"name":"Ada","age":"old"}"#),
+            reply(r#"{"name":"Ada","age":0}"#),
+            reply(r#"{"name":"Ada","age":36}"#),
+        ];
+        let (url, mut requests) = recording_server(responses).await;
+        let client = LlmClient::new(ClientConfig::anthropic("", "model").base_url(url)).unwrap();
+        let options = ChatOptions {
+            structured_repairs: 1,
+            ..ChatOptions::default()
+        };
+
+        let error = client
+            .complete_structured_with::<Person>("Who?", "person", options)
+            .await
+            .unwrap_err();
+        let LlmError::StructuredRepairFailed { attempts, usage } = error else {
+            panic!("unexpected error: {error}");
+        };
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts[0].error.contains("age"));
+        assert!(
+            attempts[1].error.starts_with("/age:"),
+            "{}",
+            attempts[1].error
+        );
+        assert_eq!(usage.input_tokens, 20);
+
+        let repaired = client
+            .complete_structured_with::<Person>("Who?", "person", options)
+            .await
+            .unwrap();
+        assert_eq!(
+            (repaired.data.name.as_str(), repaired.data.age),
+            ("Ada", 36)
+        );
+        assert_eq!(
+            (repaired.usage.input_tokens, repaired.usage.output_tokens),
+            (20, 10)
+        );
+
+        let _ = requests.recv().await.unwrap();
+        let retry = requests.recv().await.unwrap().body;
+        let messages = retry["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][0]["type"], "tool_use");
+        let feedback = &messages[2]["content"][0];
+        assert_eq!(feedback["type"], "tool_result");
+        assert_eq!(feedback["tool_use_id"], "toolu_1");
+        assert_eq!(feedback["is_error"], true);
+
+        let first = requests.recv().await.unwrap().body;
+        assert_eq!(first["messages"].as_array().unwrap().len(), 1);
+        let repair = requests.recv().await.unwrap().body;
+        let messages = repair["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][0]["input"]["age"], 0);
+        let feedback = messages[2]["content"][0]["content"].as_str().unwrap();
+        assert!(
+            feedback.contains("/age: 0 is less than the minimum of 1"),
+            "{feedback}"
+        );
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[test]
+    fn normalizes_structured_schemas_in_requests() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Reply {
+            answer: String,
+            source: Option<String>,
+        }
+
+        let messages = [Message::user_text("hi")];
+        let openai = LlmClient::new(ClientConfig::openai("key", "gpt")).expect("client");
+        let plumbing =
+            StructuredPlumbing::new::<Reply>(Provider::OpenAiCompatible, "reply").unwrap();
+        let request = openai
+            .openai_request(&messages, &plumbing.options(ChatOptions::default()))
+            .unwrap();
+        let Some(ResponseFormat::JsonSchema { json_schema }) = request.response_format else {
+            panic!("expected a JSON Schema response format");
+        };
+        assert_eq!(json_schema.schema["additionalProperties"], false);
+        assert_eq!(
+            json_schema.schema["required"],
+            serde_json::json!(["answer", "source"])
+        );
+        assert!(json_schema.schema.get("$schema").is_none());
+
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Tree {
+            children: Vec<Tree>,
+        }
+
+        let anthropic = LlmClient::new(ClientConfig::anthropic("key", "claude")).expect("client");
+        let plumbing = StructuredPlumbing::new::<Tree>(Provider::Anthropic, "tree").unwrap();
+        let request = anthropic
+            .anthropic_request(&messages, &plumbing.options(ChatOptions::default()))
+            .unwrap();
+        let tools = request.tools.expect("structured output tool");
+        let ToolDefinition::Custom(tool) = &tools[0] else {
+            panic!("expected a custom tool");
+        };
+        assert_eq!(
+            tool.input_schema["properties"]["children"]["items"]["$ref"],
+            "#"
+        );
+
+        let plumbing = StructuredPlumbing::new::<Tree>(Provider::OpenAiCompatible, "tree").unwrap();
+        let error = openai
+            .openai_request(&messages, &plumbing.options(ChatOptions::default()))
+            .unwrap_err();
+        assert!(matches!(error, LlmError::Config(message) if message.contains("tree")));
+    }
+
+    #[test]
+    fn structured_requests_default_temperature_only_without_thinking() {
+        let messages = [Message::user_text("hi")];
+        let client = LlmClient::new(ClientConfig::anthropic("key", "claude")).expect("client");
+        let plumbing = StructuredPlumbing::new::<String>(Provider::Anthropic, "reply").unwrap();
+
+        let request = client
+            .anthropic_request(&messages, &plumbing.options(ChatOptions::default()))
+            .unwrap();
+        assert_eq!(request.temperature, Some(0.0));
+
+        let thinking = ThinkingConfig::Enabled {
+            budget_tokens: 1024,
+        };
+        let options = ChatOptions {
+            thinking: Some(&thinking),
+            ..ChatOptions::default()
+        };
+        let request = client
+            .anthropic_request(&messages, &plumbing.options(options))
+            .unwrap();
+        assert_eq!(request.temperature, None);
+        let body = serde_json::to_value(&request).unwrap();
+        assert!(body.get("temperature").is_none());
+    }
 }
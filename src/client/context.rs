@@ -0,0 +1,316 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+use super::LlmClient;
+use super::chat::ChatOptions;
+use super::error::LlmError;
+use super::tokens::{Tokenizer, estimate_tokens, message_tokens};
+use crate::types::anthropic::{ContentBlock, Message, ToolResultContent};
+use crate::types::common::ToolDefinition;
+
+/// Context window of well-known models, matched by model name prefix.
+pub fn context_window(model: &str) -> Option<u64> {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    const WINDOWS: &[(&str, u64)] = &[
+        ("claude-", 200_000),
+        ("gpt-4.1", 1_047_576),
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-5", 400_000),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("gemini-", 1_048_576),
+    ];
+    WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+/// Produces a summary of the messages being replaced.
+pub type Summarizer = Arc<
+    dyn Fn(Vec<Message>) -> Pin<Box<dyn Future<Output = Result<String, LlmError>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// How [`ContextManager`] makes room in the history.
+#[derive(Clone)]
+pub enum TruncationStrategy {
+    /// Drop the oldest turns. History is only cut before a user message
+    /// without tool results, so tool_use/tool_result pairs stay together and
+    /// the last user turn is always kept.
+    DropOldest,
+    /// Replace the turns `DropOldest` would drop with a summary from the
+    /// callback, prepended to the first kept user message.
+    Summarize(Summarizer),
+    /// Shorten text tool results longer than `max_chars`, oldest first.
+    TruncateToolResults { max_chars: usize },
+}
+
+impl fmt::Debug for TruncationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DropOldest => f.write_str("DropOldest"),
+            Self::Summarize(_) => f.write_str("Summarize(..)"),
+            Self::TruncateToolResults { max_chars } => f
+                .debug_struct("TruncateToolResults")
+                .field("max_chars", max_chars)
+                .finish(),
+        }
+    }
+}
+
+/// Keeps requests within the model's context window.
+///
+/// The budget is the context window minus the request's `max_tokens`.
+/// Strategies run in order until the estimate fits; a request that still
+/// does not fit is sent anyway with a warning.
+#[derive(Debug, Clone)]
+pub struct ContextManager {
+    /// Window size; `None` looks the model up with [`context_window`] and
+    /// skips budgeting for unknown models.
+    pub context_window: Option<u64>,
+    pub strategies: Vec<TruncationStrategy>,
+}
+
+impl ContextManager {
+    pub fn new(strategies: Vec<TruncationStrategy>) -> Self {
+        Self {
+            context_window: None,
+            strategies,
+        }
+    }
+
+    #[must_use]
+    pub fn context_window(mut self, tokens: u64) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// Apply the strategies to `messages` until the request fits in `budget`
+    /// tokens as counted by `tokenizer`. Returns the messages unchanged if
+    /// they already fit.
+    pub async fn fit<'a>(
+        &self,
+        tokenizer: &dyn Tokenizer,
+        budget: u64,
+        system: Option<&str>,
+        messages: &'a [Message],
+        tools: &[ToolDefinition],
+    ) -> Result<Cow<'a, [Message]>, LlmError> {
+        let fixed = estimate_tokens(tokenizer, system, &[], tools);
+        let fits = |messages: &[Message]| {
+            fixed
+                + messages
+                    .iter()
+                    .map(|message| message_tokens(tokenizer, message))
+                    .sum::<u64>()
+                <= budget
+        };
+        if fits(messages) {
+            return Ok(Cow::Borrowed(messages));
+        }
+
+        let mut messages = messages.to_vec();
+        for strategy in &self.strategies {
+            match strategy {
+                TruncationStrategy::DropOldest => {
+                    let cut = drop_point(&messages, &fits);
+                    messages.drain(..cut);
+                }
+                TruncationStrategy::Summarize(summarize) => {
+                    let cut = drop_point(&messages, &fits);
+                    if cut > 0 {
+                        let dropped: Vec<_> = messages.drain(..cut).collect();
+                        let summary = summarize(dropped).await?;
+                        messages[0].content.insert(
+                            0,
+                            ContentBlock::text(format!(
+                                "Summary of the earlier conversation:\n{summary}"
+                            )),
+                        );
+                    }
+                }
+                TruncationStrategy::TruncateToolResults { max_chars } => {
+                    truncate_tool_results(&mut messages, *max_chars, &fits);
+                }
+            }
+            if fits(&messages) {
+                break;
+            }
+        }
+        Ok(Cow::Owned(messages))
+    }
+}
+
+/// Smallest cut before a plain user message that makes the rest fit, or the
+/// last such cut if none does.
+fn drop_point(messages: &[Message], fits: &impl Fn(&[Message]) -> bool) -> usize {
+    let cuts: Vec<usize> = (1..messages.len())
+        .filter(|&index| {
+            let message = &messages[index];
+            message.role == "user"
+                && !message
+                    .content
+                    .iter()
+                    .any(|block| matches!(block, ContentBlock::ToolResult { .. }))
+        })
+        .collect();
+    cuts.iter()
+        .copied()
+        .find(|&cut| fits(&messages[cut..]))
+        .or(cuts.last().copied())
+        .unwrap_or(0)
+}
+
+fn truncate_tool_results(
+    messages: &mut [Message],
+    max_chars: usize,
+    fits: &impl Fn(&[Message]) -> bool,
+) {
+    for index in 0..messages.len() {
+        for position in 0..messages[index].content.len() {
+            if fits(messages) {
+                return;
+            }
+            if let ContentBlock::ToolResult {
+                content: ToolResultContent::Text(text),
+                ..
+            } = &mut messages[index].content[position]
+                && let Some((cut, _)) = text.char_indices().nth(max_chars)
+            {
+                let removed = text[cut..].chars().count();
+                text.truncate(cut);
+                text.push_str(&format!("\n[truncated {removed} characters]"));
+            }
+        }
+    }
+}
+
+impl LlmClient {
+    /// Fit `messages` into the model's context window with the configured
+    /// [`ContextManager`], if any.
+    pub(crate) async fn fit_context<'a>(
+        &self,
+        messages: &'a [Message],
+        options: &ChatOptions<'_>,
+    ) -> Result<Cow<'a, [Message]>, LlmError> {
+        let Some(manager) = &self.config.context_manager else {
+            return Ok(Cow::Borrowed(messages));
+        };
+        let model = self.model(options);
+        let Some(window) = manager.context_window.or_else(|| context_window(model)) else {
+            return Ok(Cow::Borrowed(messages));
+        };
+        let max_tokens = options.max_tokens.unwrap_or(self.config.max_tokens);
+        let budget = window.saturating_sub(u64::from(max_tokens));
+        let tools = options.tools.unwrap_or_default();
+        let tokenizer = self.config.tokenizer.as_ref();
+        let fitted = manager
+            .fit(tokenizer, budget, options.system, messages, tools)
+            .await?;
+        if let Cow::Owned(fitted) = &fitted {
+            info!(
+                "Trimmed history from {} to {} messages to fit {model}'s context",
+                messages.len(),
+                fitted.len()
+            );
+            let estimate = estimate_tokens(tokenizer, options.system, fitted, tools);
+            if estimate > budget {
+                warn!("Request still needs ~{estimate} tokens of a {budget}-token budget");
+            }
+        }
+        Ok(fitted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tokens::HeuristicTokenizer;
+    use serde_json::json;
+
+    fn history() -> Vec<Message> {
+        vec![
+            Message::user_text("a".repeat(400)),
+            Message::assistant(vec![ContentBlock::tool_use(
+                "toolu_1",
+                "search",
+                json!({ "q": "x" }),
+            )]),
+            Message::tool_results(vec![ContentBlock::tool_result(
+                "toolu_1",
+                "r".repeat(400),
+                false,
+            )]),
+            Message::assistant_text("b".repeat(400)),
+            Message::user_text("latest question"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn drops_whole_turns_and_truncates_tool_results() {
+        let messages = history();
+        let manager = ContextManager::new(vec![TruncationStrategy::DropOldest]);
+        let unchanged = manager
+            .fit(&HeuristicTokenizer, 10_000, None, &messages, &[])
+            .await
+            .unwrap();
+        assert!(matches!(unchanged, Cow::Borrowed(_)));
+
+        let fitted = manager
+            .fit(&HeuristicTokenizer, 50, None, &messages, &[])
+            .await
+            .unwrap();
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(fitted[0].content.len(), 1);
+
+        let manager = ContextManager::new(vec![TruncationStrategy::TruncateToolResults {
+            max_chars: 20,
+        }]);
+        let fitted = manager
+            .fit(&HeuristicTokenizer, 250, None, &messages, &[])
+            .await
+            .unwrap();
+        assert_eq!(fitted.len(), 5);
+        let ContentBlock::ToolResult {
+            content: ToolResultContent::Text(text),
+            ..
+        } = &fitted[2].content[0]
+        else {
+            panic!("expected a text tool result");
+        };
+        assert_eq!(
+            text,
+            &format!("{}\n[truncated 380 characters]", "r".repeat(20))
+        );
+    }
+
+    #[tokio::test]
+    async fn summarizes_dropped_turns_into_the_first_kept_message() {
+        let messages = history();
+        let summarize: Summarizer = Arc::new(|dropped: Vec<Message>| {
+            Box::pin(async move { Ok(format!("{} earlier messages", dropped.len())) })
+        });
+        let manager = ContextManager::new(vec![TruncationStrategy::Summarize(summarize)]);
+        let fitted = manager
+            .fit(&HeuristicTokenizer, 50, None, &messages, &[])
+            .await
+            .unwrap();
+
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(fitted[0].role, "user");
+        let ContentBlock::Text { text, .. } = &fitted[0].content[0] else {
+            panic!("expected the summary first");
+        };
+        assert!(text.ends_with("4 earlier messages"));
+        assert_eq!(context_window("anthropic/claude-sonnet-4"), Some(200_000));
+    }
+}
@@ -95,6 +95,14 @@ impl EmbeddingsConfig {
     }
 }
 
+/// Per-request overrides for an embeddings call.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbeddingsOptions<'a> {
+    pub model: Option<&'a str>,
+    pub dimensions: Option<u32>,
+    pub timeout: Option<Duration>,
+}
+
 /// Embeddings client (OpenAI-compatible API only).
 pub struct EmbeddingsClient {
     inner: LlmClient,
@@ -122,14 +130,25 @@ impl EmbeddingsClient {
     pub async fn create_embeddings(
         &self,
         texts: &[impl AsRef<str>],
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        self.create_embeddings_with(texts, EmbeddingsOptions::default())
+            .await
+    }
+
+    /// Like [`create_embeddings`](Self::create_embeddings), overriding the
+    /// configured model, dimensions or timeout for this request.
+    pub async fn create_embeddings_with(
+        &self,
+        texts: &[impl AsRef<str>],
+        options: EmbeddingsOptions<'_>,
     ) -> Result<Vec<Vec<f32>>, LlmError> {
         let expected_count = texts.len();
         let input: Vec<String> = texts.iter().map(|t| t.as_ref().to_string()).collect();
 
         let request = EmbeddingsRequest {
-            model: self.config.model.clone(),
+            model: options.model.unwrap_or(&self.config.model).to_string(),
             input,
-            dimensions: self.config.dimensions,
+            dimensions: options.dimensions.or(self.config.dimensions),
             input_type: self.config.input_type.clone(),
             encoding_format: self.config.encoding_format.clone(),
         };
@@ -137,10 +156,13 @@ impl EmbeddingsClient {
         let url = self.inner.endpoint("embeddings");
         debug!(
             "POST {url} (model: {}, count: {expected_count})",
-            self.config.model
+            request.model
         );
 
-        let body = self.inner.send_json(&url, &request).await?;
+        let body = self
+            .inner
+            .send_json(&url, &request, options.timeout)
+            .await?;
         let resp: EmbeddingsResponse = serde_json::from_slice(&body)
             .map_err(|error| LlmError::ParseResponse(error.to_string()))?;
 
@@ -174,3 +196,59 @@ impl EmbeddingsClient {
         results.pop().ok_or(LlmError::EmptyResponse)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::test_server::{Reply, json_response, recording_server};
+
+    #[tokio::test]
+    async fn applies_per_request_overrides() {
+        let (url, mut requests) = recording_server(vec![
+            Reply::Stall(String::new()),
+            Reply::Respond(json_response(
+                r#"{"data":[{"index":1,"embedding":[0.5]},{"index":0,"embedding":[0.25]}]}"#,
+            )),
+        ])
+        .await;
+        let client = EmbeddingsClient::new(
+            EmbeddingsConfig::openai_compatible(url, "", "embed-large").dimensions(1024),
+        )
+        .unwrap();
+
+        let error = client
+            .create_embeddings_with(
+                &["a"],
+                EmbeddingsOptions {
+                    timeout: Some(Duration::from_millis(50)),
+                    ..EmbeddingsOptions::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, LlmError::Request(error) if error.is_timeout()),
+            "{error}"
+        );
+
+        let vectors = client
+            .create_embeddings_with(
+                &["a", "b"],
+                EmbeddingsOptions {
+                    model: Some("embed-small"),
+                    dimensions: Some(256),
+                    ..EmbeddingsOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(vectors, [vec![0.25], vec![0.5]]);
+        let timed_out = requests.recv().await.unwrap().body;
+        assert_eq!(timed_out["model"], "embed-large");
+        assert_eq!(timed_out["dimensions"], 1024);
+        let overridden = requests.recv().await.unwrap().body;
+        assert_eq!(overridden["model"], "embed-small");
+        assert_eq!(overridden["dimensions"], 256);
+        assert_eq!(overridden["input"], serde_json::json!(["a", "b"]));
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::types::common::Usage;
+
 #[derive(Debug, Error)]
 pub enum LlmError {
     #[error("Invalid client configuration: {0}")]
@@ -10,8 +12,16 @@ pub enum LlmError {
     #[error("Request failed: {0}")]
     Request(#[from] reqwest::Error),
 
-    #[error("API error ({status}): {body}")]
-    ApiError { status: u16, body: String },
+    #[error("API error ({status}, {kind:?}): {message}")]
+    ApiError {
+        status: u16,
+        kind: ApiErrorKind,
+        /// Provider-supplied message, or the raw body when it is not JSON.
+        message: String,
+        body: String,
+        /// `request-id` (Anthropic) or `x-request-id` (OpenAI) header.
+        request_id: Option<String>,
+    },
 
     #[error("Failed to parse response: {0}")]
     ParseResponse(String),
@@ -19,6 +29,13 @@ pub enum LlmError {
     #[error("Structured response did not match the requested schema: {error}; body: {body}")]
     InvalidStructuredOutput { error: String, body: String },
 
+    #[error("Structured response still invalid after {} attempts: {}", attempts.len(), attempts.last().map_or("", |attempt| attempt.error.as_str()))]
+    StructuredRepairFailed {
+        attempts: Vec<StructuredAttempt>,
+        /// Usage summed over all attempts.
+        usage: Usage,
+    },
+
     #[error("Empty response from API")]
     EmptyResponse,
 
@@ -30,4 +47,206 @@ pub enum LlmError {
 
     #[error("Streaming protocol error: {0}")]
     Stream(String),
+
+    #[error("No stream event received for {0:?}")]
+    StreamIdleTimeout(std::time::Duration),
+
+    #[error("Request was cancelled")]
+    Cancelled,
+}
+
+impl LlmError {
+    /// Build an [`LlmError::ApiError`] from a non-success response.
+    pub fn api(status: u16, body: String, request_id: Option<String>) -> Self {
+        let (kind, message) = parse_error_body(status, &body);
+        Self::ApiError {
+            status,
+            kind,
+            message,
+            body,
+            request_id,
+        }
+    }
+
+    /// Whether repeating the same request may succeed: rate limits,
+    /// overload, server errors, request timeouts, stalled streams and transport
+    /// failures.
+    ///
+    /// This ignores the transport opt-ins in [`RetryPolicy::retry_on`]; use
+    /// [`is_retryable_under`](Self::is_retryable_under) to decide whether to
+    /// actually resend.
+    ///
+    /// [`RetryPolicy::retry_on`]: super::RetryPolicy::retry_on
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::ApiError { status, kind, .. } => {
+                *status == 408
+                    || matches!(
+                        kind,
+                        ApiErrorKind::RateLimited | ApiErrorKind::Overloaded | ApiErrorKind::Server
+                    )
+            }
+            Self::Request(error) => super::TransportErrorKind::classify(error).is_some(),
+            Self::StreamIdleTimeout(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether `policy` allows resending the request after this error.
+    /// Retryable responses always qualify; transport failures and stalled
+    /// streams only for the classes enabled in `retry_on`.
+    pub fn is_retryable_under(&self, policy: &super::RetryPolicy) -> bool {
+        match self {
+            Self::Request(error) => policy.retries_transport_error(error),
+            Self::StreamIdleTimeout(_) => policy.retry_on.timeout,
+            _ => self.is_retryable(),
+        }
+    }
+}
+
+/// One rejected structured output, reported by
+/// [`LlmError::StructuredRepairFailed`].
+#[derive(Debug, Clone)]
+pub struct StructuredAttempt {
+    pub error: String,
+    /// Output that failed, truncated to 4 KiB.
+    pub body: String,
+}
+
+/// Provider-neutral category of an API error response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorKind {
+    RateLimited,
+    Overloaded,
+    Authentication,
+    /// Missing permissions, including OpenAI `insufficient_quota`.
+    PermissionDenied,
+    InvalidRequest,
+    ContextLengthExceeded,
+    NotFound,
+    ContentFiltered,
+    Server,
+    Other,
+}
+
+/// Classify Anthropic (`{"type":"error","error":{"type","message"}}`) and
+/// OpenAI (`{"error":{"code","type","message","param"}}`) error bodies,
+/// falling back to the status code.
+fn parse_error_body(status: u16, body: &str) -> (ApiErrorKind, String) {
+    let value: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+    let error = value.get("error").unwrap_or(&serde_json::Value::Null);
+    let field = |name: &str| {
+        error
+            .get(name)
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+    };
+    let (error_type, code) = (field("type"), field("code"));
+    let message = error
+        .get("message")
+        .and_then(serde_json::Value::as_str)
+        .or_else(|| error.as_str())
+        .unwrap_or(body)
+        .to_string();
+    let lower = message.to_lowercase();
+    let is = |name: &str| error_type == name || code == name;
+
+    let kind = if is("context_length_exceeded")
+        || lower.contains("prompt is too long")
+        || lower.contains("context length")
+        || lower.contains("context window")
+        || lower.contains("exceed context limit")
+    {
+        ApiErrorKind::ContextLengthExceeded
+    } else if is("content_filter") || is("content_policy_violation") {
+        ApiErrorKind::ContentFiltered
+    } else if is("insufficient_quota") {
+        ApiErrorKind::PermissionDenied
+    } else if is("overloaded_error") || status == 529 {
+        ApiErrorKind::Overloaded
+    } else if is("rate_limit_error") || is("rate_limit_exceeded") || status == 429 {
+        ApiErrorKind::RateLimited
+    } else if is("authentication_error") || is("invalid_api_key") || status == 401 {
+        ApiErrorKind::Authentication
+    } else if is("permission_error") || status == 403 {
+        ApiErrorKind::PermissionDenied
+    } else if is("not_found_error") || status == 404 {
+        ApiErrorKind::NotFound
+    } else if is("invalid_request_error")
+        || is("request_too_large")
+        || matches!(status, 400 | 413 | 422)
+    {
+        ApiErrorKind::InvalidRequest
+    } else if is("api_error") || is("server_error") || status >= 500 {
+        ApiErrorKind::Server
+    } else {
+        ApiErrorKind::Other
+    };
+    (kind, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RetryPolicy;
+
+    #[test]
+    fn classifies_provider_error_bodies() {
+        let error = LlmError::api(
+            400,
+            r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#.into(),
+            Some("req_1".into()),
+        );
+        assert!(matches!(
+            &error,
+            LlmError::ApiError {
+                kind: ApiErrorKind::ContextLengthExceeded,
+                request_id: Some(id),
+                ..
+            } if id == "req_1"
+        ));
+        assert!(!error.is_retryable());
+
+        let error = LlmError::api(
+            529,
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#.into(),
+            None,
+        );
+        assert!(matches!(
+            &error,
+            LlmError::ApiError { kind: ApiErrorKind::Overloaded, message, .. } if message == "Overloaded"
+        ));
+        assert!(error.is_retryable());
+
+        let error = LlmError::api(
+            429,
+            r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota","param":null,"code":"insufficient_quota"}}"#.into(),
+            None,
+        );
+        assert!(matches!(
+            error,
+            LlmError::ApiError {
+                kind: ApiErrorKind::PermissionDenied,
+                ..
+            }
+        ));
+        assert!(!error.is_retryable());
+
+        let error = LlmError::api(502, "<html>Bad Gateway</html>".into(), None);
+        assert!(matches!(
+            error,
+            LlmError::ApiError {
+                kind: ApiErrorKind::Server,
+                ..
+            }
+        ));
+        assert!(error.is_retryable_under(&RetryPolicy::default()));
+
+        let stalled = LlmError::StreamIdleTimeout(std::time::Duration::from_secs(1));
+        let mut policy = RetryPolicy::default();
+        assert!(stalled.is_retryable());
+        assert!(!stalled.is_retryable_under(&policy));
+        policy.retry_on.timeout = true;
+        assert!(stalled.is_retryable_under(&policy));
+    }
 }
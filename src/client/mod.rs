@@ -1,23 +1,39 @@
 use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crate::types::common::Provider;
 
 pub mod chat;
+pub mod context;
 #[cfg(feature = "embeddings")]
 pub mod embeddings;
 pub mod error;
+pub mod models;
+pub mod rate_limit;
 #[cfg(feature = "rig")]
 pub mod rig;
 #[cfg(feature = "streaming")]
 pub mod streaming;
+#[cfg(test)]
+pub(crate) mod test_server;
+pub mod tokens;
+pub mod tools;
 
 pub use chat::{ChatOptions, StructuredResponse};
+pub use context::{ContextManager, Summarizer, TruncationStrategy};
 #[cfg(feature = "embeddings")]
-pub use embeddings::{EmbeddingsClient, EmbeddingsConfig};
-pub use error::LlmError;
+pub use embeddings::{EmbeddingsClient, EmbeddingsConfig, EmbeddingsOptions};
+pub use error::{ApiErrorKind, LlmError, StructuredAttempt};
+pub use models::{Capabilities, ModelInfo};
+pub use rate_limit::RateLimitInfo;
 #[cfg(feature = "streaming")]
-pub use streaming::{ChatStream, StreamEvent};
+pub use streaming::{
+    AccumulatingStream, BlockKind, CancelHandle, ChatStream, StreamAccumulator, StreamEvent,
+    StructuredEvent, StructuredStream, ToolCallTracker,
+};
+pub use tokens::{HeuristicTokenizer, TokenCount, Tokenizer};
+pub use tools::{Tool, ToolRegistry, ToolRun};
 
 const DEFAULT_MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
 
@@ -28,11 +44,26 @@ pub enum AuthScheme {
     None,
 }
 
+/// Retry behavior for retryable responses and transport failures.
+///
+/// The delay is the server's `retry-after` / rate-limit reset hint when one
+/// is present, otherwise exponential backoff with optional jitter. Either way
+/// it is capped by `max_backoff`.
+///
+/// Responses for which [`LlmError::is_retryable`] holds (408, rate limits,
+/// overload, server errors) are always retried. Transport failures are
+/// retried only for the classes enabled in `retry_on`.
 #[derive(Debug, Clone)]
 pub struct RetryPolicy {
     pub max_retries: u32,
     pub initial_backoff: Duration,
     pub max_backoff: Duration,
+    pub jitter: Jitter,
+    pub retry_on: TransportRetries,
+    /// Restart a stream from scratch when it fails, with an error this
+    /// policy retries, before any content was emitted. Events up to the first content delta
+    /// are held back so a restart never duplicates output.
+    pub restart_stream_before_content: bool,
 }
 
 impl Default for RetryPolicy {
@@ -41,10 +72,162 @@ impl Default for RetryPolicy {
             max_retries: 2,
             initial_backoff: Duration::from_millis(250),
             max_backoff: Duration::from_secs(4),
+            jitter: Jitter::None,
+            retry_on: TransportRetries::default(),
+            restart_stream_before_content: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a failed send or body read should be retried under this policy.
+    pub fn retries_transport_error(&self, error: &reqwest::Error) -> bool {
+        match TransportErrorKind::classify(error) {
+            Some(TransportErrorKind::Connection) => self.retry_on.connection,
+            Some(TransportErrorKind::Timeout) => self.retry_on.timeout,
+            Some(TransportErrorKind::IncompleteBody) => self.retry_on.incomplete_body,
+            None => false,
+        }
+    }
+
+    /// Delay before retry number `attempt` (0-based).
+    pub fn delay(&self, attempt: u32, server_hint: Option<Duration>) -> Duration {
+        if let Some(hint) = server_hint {
+            return hint.min(self.max_backoff);
+        }
+        let factor = 1u32 << attempt.min(16);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+        self.jitter.apply(backoff)
+    }
+}
+
+/// Transport failure classes that [`RetryPolicy`] may retry. All are off by
+/// default because a timed-out or cut-off request may already have been
+/// processed (and billed) by the provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportRetries {
+    /// DNS and connect failures, resets, and connections closed before a
+    /// response arrived.
+    pub connection: bool,
+    /// The request or response exceeded its timeout.
+    pub timeout: bool,
+    /// The response body was interrupted while being read.
+    pub incomplete_body: bool,
+}
+
+impl TransportRetries {
+    /// Retry every transport failure class.
+    pub fn all() -> Self {
+        Self {
+            connection: true,
+            timeout: true,
+            incomplete_body: true,
+        }
+    }
+}
+
+/// Classification of a [`reqwest::Error`] for retry purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportErrorKind {
+    Connection,
+    Timeout,
+    IncompleteBody,
+}
+
+impl TransportErrorKind {
+    /// Classify a transport error. Builder, redirect, undecodable-content
+    /// and other non-transient errors return `None`.
+    pub fn classify(error: &reqwest::Error) -> Option<Self> {
+        if error.is_timeout() {
+            Some(Self::Timeout)
+        } else if error.is_connect() || is_connection_lost(error) {
+            Some(Self::Connection)
+        } else if error.is_body() || (error.is_decode() && is_read_interrupted(error)) {
+            Some(Self::IncompleteBody)
+        } else {
+            None
+        }
+    }
+}
+
+fn sources(error: &reqwest::Error) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+    std::iter::successors(std::error::Error::source(error), |error| error.source())
+}
+
+/// Whether the connection was reset or closed before a response arrived.
+fn is_connection_lost(error: &reqwest::Error) -> bool {
+    sources(error).any(|source| {
+        source
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|error| {
+                matches!(
+                    error.kind(),
+                    std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::BrokenPipe
+                )
+            })
+            || source
+                .downcast_ref::<hyper::Error>()
+                .is_some_and(hyper::Error::is_incomplete_message)
+    })
+}
+
+/// Whether a body read failed in the connection rather than on its content.
+/// reqwest reports both as decode errors.
+fn is_read_interrupted(error: &reqwest::Error) -> bool {
+    sources(error).any(|source| source.is::<hyper::Error>() || source.is::<std::io::Error>())
+}
+
+/// Randomization applied to computed exponential backoff.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Jitter {
+    /// Use the exact backoff.
+    #[default]
+    None,
+    /// Uniform in `[0, backoff]`.
+    Full,
+    /// Uniform in `[backoff / 2, backoff]`.
+    Equal,
+}
+
+impl Jitter {
+    fn apply(self, backoff: Duration) -> Duration {
+        match self {
+            Self::None => backoff,
+            Self::Full => backoff.mul_f64(random_fraction()),
+            Self::Equal => backoff / 2 + (backoff / 2).mul_f64(random_fraction()),
         }
     }
 }
 
+/// Uniform value in `[0, 1)` without pulling in an RNG dependency.
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// What to do with a request parameter the target provider does not support
+/// (e.g. `seed` on Anthropic, `top_k` on OpenAI).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnsupportedParamPolicy {
+    /// Omit the parameter and log a warning.
+    #[default]
+    Drop,
+    /// Fail the request with [`LlmError::Config`].
+    Error,
+}
+
 /// Configuration for the LLM client.
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
@@ -58,6 +241,16 @@ pub struct ClientConfig {
     pub headers: BTreeMap<String, String>,
     pub retry_policy: RetryPolicy,
     pub max_response_bytes: usize,
+    pub unsupported_params: UnsupportedParamPolicy,
+    /// Longest gap between streamed events before the stream fails with
+    /// [`LlmError::StreamIdleTimeout`]. `None` waits indefinitely.
+    pub stream_idle_timeout: Option<Duration>,
+    /// Trims the history to fit the model's context window before each
+    /// `chat`/`chat_stream` request. `None` sends it unchanged.
+    pub context_manager: Option<ContextManager>,
+    /// Local token counter for `count_tokens` on OpenAI-compatible providers
+    /// and for context budgeting.
+    pub tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl ClientConfig {
@@ -74,6 +267,10 @@ impl ClientConfig {
             headers: BTreeMap::new(),
             retry_policy: RetryPolicy::default(),
             max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            unsupported_params: UnsupportedParamPolicy::default(),
+            stream_idle_timeout: None,
+            context_manager: None,
+            tokenizer: Arc::new(HeuristicTokenizer),
         }
     }
 
@@ -94,6 +291,10 @@ impl ClientConfig {
             headers: BTreeMap::new(),
             retry_policy: RetryPolicy::default(),
             max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            unsupported_params: UnsupportedParamPolicy::default(),
+            stream_idle_timeout: None,
+            context_manager: None,
+            tokenizer: Arc::new(HeuristicTokenizer),
         }
     }
 
@@ -160,6 +361,30 @@ impl ClientConfig {
         self.max_response_bytes = bytes.max(1);
         self
     }
+
+    #[must_use]
+    pub fn unsupported_params(mut self, policy: UnsupportedParamPolicy) -> Self {
+        self.unsupported_params = policy;
+        self
+    }
+
+    #[must_use]
+    pub fn stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    pub fn context_manager(mut self, manager: ContextManager) -> Self {
+        self.context_manager = Some(manager);
+        self
+    }
+
+    #[must_use]
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Arc::new(tokenizer);
+        self
+    }
 }
 
 /// The main LLM client.
@@ -167,6 +392,7 @@ impl ClientConfig {
 pub struct LlmClient {
     pub(crate) http: reqwest::Client,
     pub(crate) config: ClientConfig,
+    rate_limit: Arc<Mutex<Option<RateLimitInfo>>>,
 }
 
 impl LlmClient {
@@ -176,7 +402,11 @@ impl LlmClient {
             .timeout(config.timeout)
             .build()
             .map_err(|e| LlmError::Client(e.to_string()))?;
-        Ok(Self { http, config })
+        Ok(Self {
+            http,
+            config,
+            rate_limit: Arc::default(),
+        })
     }
 
     /// Get a reference to the client config.
@@ -184,6 +414,25 @@ impl LlmClient {
         &self.config
     }
 
+    /// Rate-limit headers of the most recent response that carried any.
+    /// Shared between clones of this client.
+    pub fn last_rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limit
+            .lock()
+            .map(|snapshot| snapshot.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn record_rate_limit(&self, headers: &reqwest::header::HeaderMap) -> RateLimitInfo {
+        let info = RateLimitInfo::from_headers(headers);
+        if !info.is_empty()
+            && let Ok(mut snapshot) = self.rate_limit.lock()
+        {
+            *snapshot = Some(info.clone());
+        }
+        info
+    }
+
     pub(crate) fn endpoint(&self, path: &str) -> String {
         format!(
             "{}/{}",
@@ -192,10 +441,14 @@ impl LlmClient {
         )
     }
 
-    pub(crate) fn request(&self, url: &str) -> Result<reqwest::RequestBuilder, LlmError> {
+    pub(crate) fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> Result<reqwest::RequestBuilder, LlmError> {
         let mut request = self
             .http
-            .post(url)
+            .request(method, url)
             .header("content-type", "application/json");
         request = match &self.config.auth_scheme {
             AuthScheme::Bearer if !self.config.api_key.is_empty() => {
@@ -227,50 +480,160 @@ impl LlmClient {
         Ok(request)
     }
 
-    pub(crate) async fn send_json<T: serde::Serialize + ?Sized>(
+    /// POST a prepared body (GET without one), retrying transport failures
+    /// allowed by the retry policy. Status codes are left to the caller.
+    pub(crate) async fn send_with_retry(
         &self,
         url: &str,
-        body: &T,
-    ) -> Result<Vec<u8>, LlmError> {
-        let body = serde_json::to_vec(body).map_err(|error| LlmError::Client(error.to_string()))?;
-        let mut attempt = 0;
+        body: Option<&[u8]>,
+        timeout: Option<Duration>,
+        attempt: &mut u32,
+    ) -> Result<reqwest::Response, LlmError> {
         loop {
-            let response = self.request(url)?.body(body.clone()).send().await?;
+            let mut request = match body {
+                Some(body) => self
+                    .request(reqwest::Method::POST, url)?
+                    .body(body.to_vec()),
+                None => self.request(reqwest::Method::GET, url)?,
+            };
+            if let Some(timeout) = timeout {
+                request = request.timeout(timeout);
+            }
+            match request.send().await {
+                Ok(response) => return Ok(response),
+                Err(error) => self.backoff_transport_error(error, attempt).await?,
+            }
+        }
+    }
+
+    /// Sleep before the next attempt if `error` is retryable and attempts
+    /// remain, otherwise return it.
+    async fn backoff_transport_error(
+        &self,
+        error: reqwest::Error,
+        attempt: &mut u32,
+    ) -> Result<(), LlmError> {
+        let policy = &self.config.retry_policy;
+        if *attempt >= policy.max_retries || !policy.retries_transport_error(&error) {
+            return Err(error.into());
+        }
+        let delay = policy.delay(*attempt, None);
+        *attempt += 1;
+        tracing::warn!(%error, attempt = *attempt, ?delay, "retrying LLM request after transport error");
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+
+    /// POST a prepared body (GET without one) until a 2xx arrives, retrying
+    /// retryable statuses and transport failures under the retry policy.
+    pub(crate) async fn send_checked(
+        &self,
+        url: &str,
+        body: Option<&[u8]>,
+        timeout: Option<Duration>,
+        attempt: &mut u32,
+    ) -> Result<reqwest::Response, LlmError> {
+        loop {
+            let response = self.send_with_retry(url, body, timeout, attempt).await?;
             let status = response.status();
-            let retryable =
-                status.as_u16() == 408 || status.as_u16() == 429 || status.is_server_error();
-            if retryable && attempt < self.config.retry_policy.max_retries {
-                let shift = attempt.min(16);
-                let factor = 1u32 << shift;
+            let rate_limit = self.record_rate_limit(response.headers());
+            if status.is_success() {
+                return Ok(response);
+            }
+            let request_id = request_id(response.headers());
+            let bytes = match response.bytes().await {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    self.backoff_transport_error(error, attempt).await?;
+                    continue;
+                }
+            };
+            if bytes.len() > self.config.max_response_bytes {
+                return Err(LlmError::ResponseTooLarge {
+                    limit: self.config.max_response_bytes,
+                    actual: bytes.len(),
+                });
+            }
+            let error = LlmError::api(
+                status.as_u16(),
+                String::from_utf8_lossy(&bytes).into_owned(),
+                request_id,
+            );
+            if error.is_retryable_under(&self.config.retry_policy)
+                && *attempt < self.config.retry_policy.max_retries
+            {
                 let delay = self
                     .config
                     .retry_policy
-                    .initial_backoff
-                    .saturating_mul(factor)
-                    .min(self.config.retry_policy.max_backoff);
-                attempt += 1;
-                tracing::warn!(%status, attempt, ?delay, "retrying LLM request");
+                    .delay(*attempt, rate_limit.retry_delay());
+                *attempt += 1;
+                tracing::warn!(%status, attempt = *attempt, ?delay, "retrying LLM request");
                 tokio::time::sleep(delay).await;
                 continue;
             }
-            let bytes = response.bytes().await?;
+            return Err(error);
+        }
+    }
+
+    /// POST a JSON body with retries. `timeout` overrides the configured
+    /// client timeout for this request.
+    pub(crate) async fn send_json<T: serde::Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &T,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, LlmError> {
+        let body = serde_json::to_vec(body).map_err(|error| LlmError::Client(error.to_string()))?;
+        self.fetch(url, Some(&body), timeout).await
+    }
+
+    /// GET a JSON response with retries.
+    pub(crate) async fn get_json(
+        &self,
+        url: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, LlmError> {
+        self.fetch(url, None, timeout).await
+    }
+
+    /// Read a successful response body within `max_response_bytes`.
+    async fn fetch(
+        &self,
+        url: &str,
+        body: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, LlmError> {
+        let mut attempt = 0;
+        loop {
+            let response = self.send_checked(url, body, timeout, &mut attempt).await?;
+            let bytes = match response.bytes().await {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    self.backoff_transport_error(error, &mut attempt).await?;
+                    continue;
+                }
+            };
             if bytes.len() > self.config.max_response_bytes {
                 return Err(LlmError::ResponseTooLarge {
                     limit: self.config.max_response_bytes,
                     actual: bytes.len(),
                 });
             }
-            if !status.is_success() {
-                return Err(LlmError::ApiError {
-                    status: status.as_u16(),
-                    body: String::from_utf8_lossy(&bytes).into_owned(),
-                });
-            }
             return Ok(bytes.to_vec());
         }
     }
 }
 
+/// Provider request ID for support tickets: `request-id` on Anthropic,
+/// `x-request-id` on OpenAI.
+pub(crate) fn request_id(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get("request-id")
+        .or_else(|| headers.get("x-request-id"))
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
 pub(crate) fn normalized_api_base(config: &ClientConfig) -> String {
     let base = config.base_url.trim().trim_end_matches('/');
     match config.provider {
@@ -309,6 +672,7 @@ fn validate_base_url(value: &str) -> Result<(), LlmError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::test_server::{Reply, json_response, recording_server};
 
     #[test]
     fn endpoint_contract_normalizes_pasted_provider_urls() {
@@ -346,4 +710,80 @@ mod tests {
             .is_err()
         );
     }
+
+    #[tokio::test]
+    async fn classifies_transport_errors() {
+        let http = reqwest::Client::new();
+        let (url, _) = recording_server(vec![
+            Reply::Hangup,
+            Reply::Stall(String::new()),
+            Reply::Respond(
+                "HTTP/1.1 200 OK\r\ncontent-length: 10\r\nconnection: close\r\n\r\n{}".to_string(),
+            ),
+            Reply::Respond(json_response("not json")),
+        ])
+        .await;
+
+        let error = http.get(&url).send().await.unwrap_err();
+        assert_eq!(
+            TransportErrorKind::classify(&error),
+            Some(TransportErrorKind::Connection)
+        );
+        let error = http
+            .get(&url)
+            .timeout(Duration::from_millis(50))
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            TransportErrorKind::classify(&error),
+            Some(TransportErrorKind::Timeout)
+        );
+        let response = http.get(&url).send().await.unwrap();
+        let error = response.bytes().await.unwrap_err();
+        assert_eq!(
+            TransportErrorKind::classify(&error),
+            Some(TransportErrorKind::IncompleteBody)
+        );
+        let response = http.get(&url).send().await.unwrap();
+        let error = response.json::<serde_json::Value>().await.unwrap_err();
+        assert!(error.is_decode());
+        assert_eq!(TransportErrorKind::classify(&error), None);
+    }
+
+    #[tokio::test]
+    async fn retries_connections_closed_before_response_when_enabled() {
+        let (url, _) = recording_server(vec![
+            Reply::Hangup,
+            Reply::Hangup,
+            Reply::Respond(json_response("{}")),
+        ])
+        .await;
+
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let config = ClientConfig::openai_compatible(format!("{url}/v1"), "", "model");
+        let client = LlmClient::new(config.clone().retry_policy(policy.clone())).unwrap();
+        let url = client.endpoint("chat/completions");
+        assert!(matches!(
+            client.send_json(&url, &serde_json::json!({}), None).await,
+            Err(LlmError::Request(_))
+        ));
+
+        let client = LlmClient::new(config.retry_policy(RetryPolicy {
+            retry_on: TransportRetries {
+                connection: true,
+                ..TransportRetries::default()
+            },
+            ..policy
+        }))
+        .unwrap();
+        let body = client
+            .send_json(&url, &serde_json::json!({}), None)
+            .await
+            .unwrap();
+        assert_eq!(body, b"{}");
+    }
 }
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{debug, info};
+
+use super::chat::ChatOptions;
+use super::error::{ApiErrorKind, LlmError};
+use super::{LlmClient, normalized_api_base};
+use crate::convert::to_openai;
+use crate::types::anthropic::{ContentBlock, Message};
+use crate::types::common::{Provider, ResponseFormat, ThinkingConfig, ToolChoice, ToolDefinition};
+use crate::types::openai::ChatRequest;
+
+/// A model offered by the provider, normalized across `/models` formats.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owned_by: Option<String>,
+    /// Context window, where the listing reports one (e.g. OpenRouter).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+}
+
+/// What a provider and model actually support, as detected by
+/// [`LlmClient::probe_capabilities`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Returns a call to a forced function tool.
+    pub tool_calling: bool,
+    /// Streams a text response. Only probed with the `streaming` feature.
+    pub streaming: bool,
+    /// Honors a strict `response_format.json_schema`. Always `false` on the
+    /// native Anthropic transport, where structured output uses a tool.
+    pub json_schema: bool,
+    /// Returns thinking blocks or reports reasoning tokens.
+    pub reasoning: bool,
+    /// Reports token usage on responses.
+    pub usage: bool,
+}
+
+const PROBE_TOOL: &str = "report_probe";
+
+/// Probe results by normalized API base and model, shared by every client in
+/// the process.
+static CAPABILITIES: LazyLock<Mutex<HashMap<(String, String), Capabilities>>> =
+    LazyLock::new(Mutex::default);
+
+impl LlmClient {
+    /// List the models the provider offers.
+    ///
+    /// Reads Anthropic `/v1/models` (following pagination) or OpenAI-style
+    /// `/models`. OpenAI-compatible servers without `/models` that look like
+    /// Ollama (`.../v1` base) fall back to its native `/api/tags`.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmError> {
+        match self.config.provider {
+            Provider::Anthropic => {
+                let mut models = Vec::new();
+                let mut after = None;
+                loop {
+                    let mut url = self.endpoint("v1/models?limit=1000");
+                    if let Some(after) = &after {
+                        url.push_str(&format!("&after_id={after}"));
+                    }
+                    let page: Value = parse(&self.get_json(&url, None).await?)?;
+                    models.extend(parse_models(&page));
+                    match page["last_id"].as_str() {
+                        Some(last) if page["has_more"] == true => after = Some(last.to_string()),
+                        _ => return Ok(models),
+                    }
+                }
+            }
+            Provider::OpenAiCompatible => {
+                let body = match self.get_json(&self.endpoint("models"), None).await {
+                    Err(LlmError::ApiError {
+                        kind: ApiErrorKind::NotFound,
+                        ..
+                    }) if normalized_api_base(&self.config).ends_with("/v1") => {
+                        let base = normalized_api_base(&self.config);
+                        let url = format!("{}/api/tags", base.trim_end_matches("/v1"));
+                        debug!("No /models endpoint, trying {url}");
+                        self.get_json(&url, None).await?
+                    }
+                    other => other?,
+                };
+                Ok(parse_models(&parse(&body)?))
+            }
+        }
+    }
+
+    /// Detect which features the configured model supports by sending
+    /// minimal requests: a plain completion (usage), a forced tool call, a
+    /// strict JSON Schema response, a thinking request and, with the
+    /// `streaming` feature, a stream.
+    ///
+    /// Costs a few small requests the first time; the result is cached per
+    /// API base and model and shared by every client in the process. A
+    /// failing plain completion is returned as an error and nothing is
+    /// cached; a failing feature probe just marks that feature unsupported.
+    pub async fn probe_capabilities(&self) -> Result<Capabilities, LlmError> {
+        self.probe_model_capabilities(&self.config.model).await
+    }
+
+    /// Like [`probe_capabilities`](Self::probe_capabilities), for a model
+    /// other than the configured one, e.g. one passed as
+    /// [`ChatOptions::model`].
+    pub async fn probe_model_capabilities(&self, model: &str) -> Result<Capabilities, LlmError> {
+        let key = (normalized_api_base(&self.config), model.to_string());
+        if let Some(cached) = CAPABILITIES
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&key).copied())
+        {
+            return Ok(cached);
+        }
+
+        let prompt = [Message::user_text("Reply with the single word OK.")];
+        let options = ChatOptions {
+            model: Some(model),
+            max_tokens: Some(64),
+            ..ChatOptions::default()
+        };
+        let basic = self.chat(&prompt, options).await?;
+        let usage = basic
+            .usage
+            .as_ref()
+            .is_some_and(|usage| usage.input_tokens > 0 || usage.output_tokens > 0);
+
+        let capabilities = Capabilities {
+            tool_calling: self.probe_tool_calling(options).await,
+            streaming: self.probe_streaming(&prompt, options).await,
+            json_schema: self.probe_json_schema(options).await,
+            reasoning: self.probe_reasoning(options).await,
+            usage,
+        };
+        info!("Probed {model} ({}): {capabilities:?}", key.0);
+        if let Ok(mut cache) = CAPABILITIES.lock() {
+            cache.insert(key, capabilities);
+        }
+        Ok(capabilities)
+    }
+
+    async fn probe_tool_calling(&self, options: ChatOptions<'_>) -> bool {
+        let prompt = [Message::user_text(format!(
+            "Call {PROBE_TOOL} with ok set to true."
+        ))];
+        let tools = [ToolDefinition::new(
+            PROBE_TOOL,
+            "Report that the probe succeeded.",
+            json!({
+                "type": "object",
+                "properties": { "ok": { "type": "boolean" } },
+                "required": ["ok"]
+            }),
+        )];
+        let choice = ToolChoice::tool(PROBE_TOOL);
+        let options = ChatOptions {
+            tools: Some(&tools),
+            tool_choice: Some(&choice),
+            ..options
+        };
+        probe_ok(self.chat(&prompt, options).await).is_some_and(|response| {
+            response.content.iter().any(
+                |block| matches!(block, ContentBlock::ToolUse { name, .. } if name == PROBE_TOOL),
+            )
+        })
+    }
+
+    async fn probe_json_schema(&self, options: ChatOptions<'_>) -> bool {
+        if self.config.provider == Provider::Anthropic {
+            return false;
+        }
+        let prompt = [Message::user_text("Answer with ok set to true.")];
+        let format = ResponseFormat::json_schema(
+            "probe",
+            json!({
+                "type": "object",
+                "properties": { "ok": { "type": "boolean" } },
+                "required": ["ok"],
+                "additionalProperties": false
+            }),
+            true,
+        );
+        let options = ChatOptions {
+            response_format: Some(&format),
+            ..options
+        };
+        probe_ok(self.chat(&prompt, options).await).is_some_and(|response| {
+            serde_json::from_str::<Value>(&response.text())
+                .is_ok_and(|value| value.get("ok").is_some_and(Value::is_boolean))
+        })
+    }
+
+    async fn probe_reasoning(&self, options: ChatOptions<'_>) -> bool {
+        let thinking = ThinkingConfig::Enabled {
+            budget_tokens: 1024,
+        };
+        let prompt = [Message::user_text(
+            "What is 17 * 23? Answer with the number.",
+        )];
+        let options = ChatOptions {
+            max_tokens: Some(2048),
+            ..options
+        };
+        let response = match self.config.provider {
+            Provider::Anthropic => {
+                let options = ChatOptions {
+                    thinking: Some(&thinking),
+                    ..options
+                };
+                self.chat(&prompt, options).await
+            }
+            // `thinking` only maps to the Messages API; ask for reasoning effort
+            // instead.
+            Provider::OpenAiCompatible => {
+                async {
+                    let request = ChatRequest {
+                        reasoning_effort: Some("low".into()),
+                        ..self.openai_request(&prompt, &options)?
+                    };
+                    let response = self.chat_openai_raw(&request).await?;
+                    to_openai::response_to_anthropic(response).map_err(LlmError::Conversion)
+                }
+                .await
+            }
+        };
+        probe_ok(response).is_some_and(|response| {
+            response.thinking_text().is_some()
+                || response
+                    .content
+                    .iter()
+                    .any(|block| matches!(block, ContentBlock::RedactedThinking { .. }))
+                || response
+                    .usage
+                    .is_some_and(|usage| usage.reasoning_tokens > 0)
+        })
+    }
+
+    #[cfg(feature = "streaming")]
+    async fn probe_streaming(&self, prompt: &[Message], options: ChatOptions<'_>) -> bool {
+        use futures_util::StreamExt;
+
+        use crate::types::stream::StreamEvent;
+
+        let Some(mut stream) = probe_ok(self.chat_stream(prompt, options).await) else {
+            return false;
+        };
+        let mut text = false;
+        while let Some(event) = stream.next().await {
+            match event {
+                Ok(StreamEvent::TextDelta { .. }) => text = true,
+                Ok(StreamEvent::Done { .. }) => return text,
+                Ok(_) => {}
+                Err(error) => {
+                    debug!("Streaming probe failed: {error}");
+                    return false;
+                }
+            }
+        }
+        false
+    }
+
+    #[cfg(not(feature = "streaming"))]
+    async fn probe_streaming(&self, _prompt: &[Message], _options: ChatOptions<'_>) -> bool {
+        false
+    }
+}
+
+fn probe_ok<T>(result: Result<T, LlmError>) -> Option<T> {
+    result
+        .inspect_err(|error| debug!("Capability probe failed: {error}"))
+        .ok()
+}
+
+fn parse(body: &[u8]) -> Result<Value, LlmError> {
+    serde_json::from_slice(body).map_err(|error| LlmError::ParseResponse(error.to_string()))
+}
+
+/// Read `data` (OpenAI, Anthropic) or `models` (Ollama `/api/tags`) entries.
+fn parse_models(body: &Value) -> Vec<ModelInfo> {
+    let entries = body["data"]
+        .as_array()
+        .or_else(|| body["models"].as_array())
+        .into_iter()
+        .flatten();
+    entries
+        .filter_map(|entry| {
+            let id = ["id", "model", "name"]
+                .iter()
+                .find_map(|key| entry[key].as_str())?;
+            let text = |key: &str| entry[key].as_str().map(str::to_string);
+            Some(ModelInfo {
+                id: id.to_string(),
+                display_name: text("display_name")
+                    .or_else(|| text("name").filter(|name| name != id)),
+                owned_by: text("owned_by"),
+                context_window: entry["context_length"]
+                    .as_u64()
+                    .or_else(|| entry["context_window"].as_u64()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientConfig;
+    use crate::client::test_server::{json_response, recording_server};
+
+    #[tokio::test]
+    async fn lists_models_across_formats() {
+        let (url, mut requests) = recording_server(vec![
+            json_response(
+                r#"{"data":[{"type":"model","id":"claude-a","display_name":"Claude A"}],"has_more":true,"last_id":"claude-a"}"#,
+            ),
+            json_response(
+                r#"{"data":[{"type":"model","id":"claude-b","display_name":"Claude B"}],"has_more":false,"last_id":"claude-b"}"#,
+            ),
+        ])
+        .await;
+        let client = LlmClient::new(ClientConfig::anthropic("", "claude-a").base_url(url)).unwrap();
+        let models = client.list_models().await.unwrap();
+        assert_eq!(
+            models
+                .iter()
+                .map(|model| model.id.as_str())
+                .collect::<Vec<_>>(),
+            ["claude-a", "claude-b"]
+        );
+        assert_eq!(models[1].display_name.as_deref(), Some("Claude B"));
+        assert_eq!(requests.recv().await.unwrap().path, "/v1/models?limit=1000");
+        assert_eq!(
+            requests.recv().await.unwrap().path,
+            "/v1/models?limit=1000&after_id=claude-a"
+        );
+
+        let not_found = "HTTP/1.1 404 Not Found\r\ncontent-type: application/json\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}";
+        let (url, mut requests) = recording_server(vec![
+            not_found.to_string(),
+            json_response(r#"{"models":[{"name":"llama3.2:latest","model":"llama3.2:latest"}]}"#),
+        ])
+        .await;
+        let client = LlmClient::new(ClientConfig::local_openai_compatible(
+            format!("{url}/v1"),
+            "llama3.2",
+        ))
+        .unwrap();
+        let models = client.list_models().await.unwrap();
+        assert_eq!(models[0].id, "llama3.2:latest");
+        assert_eq!(models[0].display_name, None);
+        assert_eq!(requests.recv().await.unwrap().path, "/v1/models");
+        assert_eq!(requests.recv().await.unwrap().path, "/api/tags");
+
+        let openrouter = parse_models(&json!({
+            "data": [{ "id": "openai/gpt-4o", "name": "GPT-4o", "context_length": 128000 }]
+        }));
+        assert_eq!(openrouter[0].display_name.as_deref(), Some("GPT-4o"));
+        assert_eq!(openrouter[0].context_window, Some(128_000));
+    }
+
+    #[cfg(feature = "streaming")]
+    #[tokio::test]
+    async fn probes_capabilities_once_per_endpoint_and_model() {
+        let completion = |message: &str| {
+            json_response(&format!(
+                r#"{{"id":"c","model":"m","choices":[{{"index":0,"message":{message},"finish_reason":"stop"}}],"usage":{{"prompt_tokens":5,"completion_tokens":1,"total_tokens":6}}}}"#
+            ))
+        };
+        let (url, mut requests) = recording_server(vec![
+            completion(r#"{"role":"assistant","content":"OK"}"#),
+            completion(
+                r#"{"role":"assistant","content":null,"tool_calls":[{"id":"call_1","type":"function","function":{"name":"report_probe","arguments":"{\"ok\":true}"}}]}"#,
+            ),
+            "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n\
+             data: {\"id\":\"c\",\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"OK\"},\"finish_reason\":null}]}\n\n\
+             data: {\"id\":\"c\",\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n\
+             data: [DONE]\n\n"
+                .to_string(),
+            completion(r#"{"role":"assistant","content":"not json"}"#),
+            completion(r#"{"role":"assistant","content":"391","reasoning_content":"17 * 23 = 391"}"#),
+        ])
+        .await;
+        let client = LlmClient::new(ClientConfig::openai_compatible(
+            url.clone(),
+            "",
+            "probe-model",
+        ))
+        .unwrap();
+
+        let capabilities = client.probe_capabilities().await.unwrap();
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                tool_calling: true,
+                streaming: true,
+                json_schema: false,
+                reasoning: true,
+                usage: true,
+            }
+        );
+        let bodies: Vec<_> = std::iter::from_fn(|| requests.try_recv().ok())
+            .map(|request| request.body)
+            .collect();
+        let offered_tools: Vec<_> = bodies.iter().map(|body| body["tools"].is_array()).collect();
+        assert_eq!(offered_tools, [false, true, false, false, false]);
+        assert_eq!(bodies[4]["reasoning_effort"], "low");
+        assert!(bodies.iter().all(|body| body["model"] == "probe-model"));
+        // Cached for the endpoint and model: the server has no responses left.
+        let same_endpoint = LlmClient::new(ClientConfig::openai_compatible(
+            format!("{url}/chat/completions"),
+            "other",
+            "other-model",
+        ))
+        .unwrap();
+        assert_eq!(
+            same_endpoint
+                .probe_model_capabilities("probe-model")
+                .await
+                .unwrap(),
+            capabilities
+        );
+        assert!(same_endpoint.probe_capabilities().await.is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Provider rate-limit headers (`retry-after`, Anthropic
+//! `anthropic-ratelimit-*`, OpenAI `x-ratelimit-*`).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::HeaderMap;
+
+/// Rate-limit snapshot parsed from the headers of one response.
+///
+/// Reset times are relative to when the response was received.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    pub requests_limit: Option<u64>,
+    pub requests_remaining: Option<u64>,
+    pub requests_reset: Option<Duration>,
+    pub tokens_limit: Option<u64>,
+    pub tokens_remaining: Option<u64>,
+    pub tokens_reset: Option<Duration>,
+    /// Explicit `retry-after-ms` / `retry-after` hint.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let text = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let number = |name: &str| text(name).and_then(|value| value.trim().parse::<u64>().ok());
+        let now = SystemTime::now();
+        let anthropic_reset = |name: &str| {
+            text(name)
+                .and_then(parse_rfc3339)
+                .map(|at| at.duration_since(now).unwrap_or_default())
+        };
+        let openai_reset = |name: &str| text(name).and_then(parse_go_duration);
+
+        let retry_after = text("retry-after-ms")
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+            .or_else(|| {
+                let value = text("retry-after")?.trim();
+                match value.parse::<u64>() {
+                    Ok(seconds) => Some(Duration::from_secs(seconds)),
+                    Err(_) => {
+                        parse_http_date(value).map(|at| at.duration_since(now).unwrap_or_default())
+                    }
+                }
+            });
+
+        Self {
+            requests_limit: number("anthropic-ratelimit-requests-limit")
+                .or_else(|| number("x-ratelimit-limit-requests")),
+            requests_remaining: number("anthropic-ratelimit-requests-remaining")
+                .or_else(|| number("x-ratelimit-remaining-requests")),
+            requests_reset: anthropic_reset("anthropic-ratelimit-requests-reset")
+                .or_else(|| openai_reset("x-ratelimit-reset-requests")),
+            tokens_limit: number("anthropic-ratelimit-tokens-limit")
+                .or_else(|| number("x-ratelimit-limit-tokens")),
+            tokens_remaining: number("anthropic-ratelimit-tokens-remaining")
+                .or_else(|| number("x-ratelimit-remaining-tokens")),
+            tokens_reset: anthropic_reset("anthropic-ratelimit-tokens-reset")
+                .or_else(|| openai_reset("x-ratelimit-reset-tokens")),
+            retry_after,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Server-suggested wait before retrying: the explicit retry-after hint,
+    /// otherwise the reset time of whichever limit is exhausted.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        if self.retry_after.is_some() {
+            return self.retry_after;
+        }
+        let requests = self
+            .requests_reset
+            .filter(|_| self.requests_remaining == Some(0));
+        let tokens = self
+            .tokens_reset
+            .filter(|_| self.tokens_remaining == Some(0));
+        requests.max(tokens)
+    }
+}
+
+/// Parse Go-style durations used by OpenAI, e.g. `1s`, `6m0s`, `20ms`, `1h2m3.5s`.
+fn parse_go_duration(value: &str) -> Option<Duration> {
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = 0.0_f64;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|character: char| !(character.is_ascii_digit() || character == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let (scale, unit_len) = if rest.starts_with("ms") {
+            (0.001, 2)
+        } else if rest.starts_with('h') {
+            (3600.0, 1)
+        } else if rest.starts_with('m') {
+            (60.0, 1)
+        } else if rest.starts_with('s') {
+            (1.0, 1)
+        } else {
+            return None;
+        };
+        total += number * scale;
+        rest = &rest[unit_len..];
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+/// Parse an RFC 3339 timestamp such as `2025-01-01T12:00:30Z` or
+/// `2025-01-01T12:00:30.5+02:00`.
+fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let (date, time) = value.split_once(['T', 't', ' '])?;
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+
+    let (clock, offset_seconds) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else {
+        let sign_at = time.rfind(['+', '-'])?;
+        let (clock, offset) = time.split_at(sign_at);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let offset = hours
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(3600)?
+            .checked_add(minutes.parse::<i64>().ok()?.checked_mul(60)?)?;
+        (clock, sign * offset)
+    };
+    let mut clock_parts = clock.splitn(3, ':');
+    let hour: i64 = clock_parts.next()?.parse().ok()?;
+    let minute: i64 = clock_parts.next()?.parse().ok()?;
+    let second: f64 = clock_parts.next()?.parse().ok()?;
+
+    let seconds = days_from_civil(year, month, day)?
+        .checked_mul(86_400)?
+        .checked_add(hour.checked_mul(3600)?)?
+        .checked_add(minute.checked_mul(60)?)?
+        .checked_sub(offset_seconds)?;
+    to_system_time(seconds as f64 + second)
+}
+
+/// Parse an IMF-fixdate HTTP date, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.split_whitespace().skip(1);
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut clock = parts.next()?.splitn(3, ':');
+    let hour: i64 = clock.next()?.parse().ok()?;
+    let minute: i64 = clock.next()?.parse().ok()?;
+    let second: i64 = clock.next()?.parse().ok()?;
+    if parts.next()? != "GMT" {
+        return None;
+    }
+    let seconds = days_from_civil(year, month, day)?
+        .checked_mul(86_400)?
+        .checked_add(hour.checked_mul(3600)?)?
+        .checked_add(minute.checked_mul(60)?)?
+        .checked_add(second)?;
+    to_system_time(seconds as f64)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era.checked_mul(146_097)?.checked_add(day_of_era - 719_468)
+}
+
+fn to_system_time(seconds_since_epoch: f64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(seconds_since_epoch).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn parses_openai_and_anthropic_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-ratelimit-limit-requests",
+            HeaderValue::from_static("500"),
+        );
+        headers.insert(
+            "x-ratelimit-remaining-requests",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert(
+            "x-ratelimit-reset-requests",
+            HeaderValue::from_static("1m30.5s"),
+        );
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("20ms"));
+        let openai = RateLimitInfo::from_headers(&headers);
+        assert_eq!(openai.requests_limit, Some(500));
+        assert_eq!(openai.requests_reset, Some(Duration::from_millis(90_500)));
+        assert_eq!(openai.tokens_reset, Some(Duration::from_millis(20)));
+        assert_eq!(openai.retry_delay(), Some(Duration::from_millis(90_500)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("7"));
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("2999-01-01T00:00:00Z"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-tokens-remaining",
+            HeaderValue::from_static("0"),
+        );
+        let anthropic = RateLimitInfo::from_headers(&headers);
+        assert_eq!(anthropic.retry_delay(), Some(Duration::from_secs(7)));
+        assert!(
+            anthropic
+                .tokens_reset
+                .is_some_and(|reset| reset > Duration::from_secs(86_400))
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        headers.insert("retry-after", HeaderValue::from_static("9"));
+        assert_eq!(
+            RateLimitInfo::from_headers(&headers).retry_after,
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn parses_timestamp_formats() {
+        assert_eq!(
+            parse_rfc3339("2015-10-21T09:28:00+02:00"),
+            parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert_eq!(
+            parse_rfc3339("1970-01-02T00:00:00.5Z"),
+            Some(UNIX_EPOCH + Duration::from_millis(86_400_500))
+        );
+        assert_eq!(parse_go_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_go_duration("soon"), None);
+    }
+
+    #[test]
+    fn ignores_out_of_range_header_values() {
+        for (name, value) in [
+            ("retry-after-ms", "1e30"),
+            ("retry-after-ms", "-5"),
+            ("retry-after-ms", "NaN"),
+            (
+                "retry-after",
+                "Wed, 21 Oct 9223372036854775807 07:28:00 GMT",
+            ),
+            ("x-ratelimit-reset-requests", "99999999999999999999s"),
+            ("x-ratelimit-reset-requests", "-1s"),
+            ("x-ratelimit-reset-requests", "NaNs"),
+            (
+                "anthropic-ratelimit-requests-reset",
+                "-9223372036854775808-01-01T00:00:00Z",
+            ),
+            (
+                "anthropic-ratelimit-requests-reset",
+                "9223372036854775807-12-31T23:59:59Z",
+            ),
+            (
+                "anthropic-ratelimit-requests-reset",
+                "2025-01-01T9223372036854775807:00:00Z",
+            ),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            let info = RateLimitInfo::from_headers(&headers);
+            assert_eq!(info.retry_after, None, "{name}: {value}");
+            assert_eq!(info.requests_reset, None, "{name}: {value}");
+        }
+    }
+}
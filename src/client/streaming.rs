@@ -1,68 +1,199 @@
+use std::collections::BTreeMap;
 use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
 
-use eventsource_stream::Eventsource;
+use eventsource_stream::{EventStreamError, Eventsource};
 use futures_core::Stream;
+use futures_util::stream::{AbortHandle, Abortable};
 use futures_util::{StreamExt, stream};
-use serde::{Deserialize, Serialize};
-
-use super::{ChatOptions, LlmClient, error::LlmError};
-use crate::convert::{thinking::build_thinking_params, to_openai};
-use crate::types::anthropic::{Message, MessagesRequest};
-use crate::types::common::{Provider, Usage};
-use crate::types::openai::ChatRequest;
-
-pub type ChatStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send>>;
-
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum StreamEvent {
-    TextDelta {
-        text: String,
-    },
-    ThinkingDelta {
-        text: String,
-    },
-    ToolCallDelta {
-        index: usize,
-        id: Option<String>,
-        name: Option<String>,
-        arguments: String,
-    },
-    ToolCallComplete {
-        index: usize,
-    },
-    Usage {
-        usage: Usage,
-    },
-    Done {
-        stop_reason: Option<String>,
-    },
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+use super::chat::StructuredPlumbing;
+use super::{ChatOptions, LlmClient, StructuredResponse, error::LlmError};
+use crate::convert::partial_json::parse_partial_json;
+use crate::convert::stream::StreamDecoder;
+use crate::types::anthropic::{ContentBlock, Message, MessagesResponse};
+use crate::types::common::{Provider, StopReason, Usage};
+pub use crate::types::stream::{BlockKind, StreamEvent};
+
+type BoxedEvents = Pin<Box<dyn Stream<Item = Result<StreamEvent, LlmError>> + Send>>;
+
+/// Normalized event stream returned by [`LlmClient::chat_stream`].
+///
+/// Fails with [`LlmError::ResponseTooLarge`] once the body exceeds
+/// `max_response_bytes`, and with [`LlmError::StreamIdleTimeout`] when no
+/// event arrives within `stream_idle_timeout`. A [`CancelHandle`] ends the
+/// stream from another task and closes the connection.
+pub struct ChatStream {
+    inner: Option<Abortable<BoxedEvents>>,
+    abort: AbortHandle,
+}
+
+impl ChatStream {
+    pub fn new(stream: impl Stream<Item = Result<StreamEvent, LlmError>> + Send + 'static) -> Self {
+        let (abort, registration) = AbortHandle::new_pair();
+        let stream: BoxedEvents = Box::pin(stream);
+        Self {
+            inner: Some(Abortable::new(stream, registration)),
+            abort,
+        }
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(self.abort.clone())
+    }
+
+    pub fn cancel(&self) {
+        self.abort.abort();
+    }
+}
+
+impl Stream for ChatStream {
+    type Item = Result<StreamEvent, LlmError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Some(inner) = self.inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match std::task::ready!(inner.poll_next_unpin(cx)) {
+            Some(item) => Poll::Ready(Some(item)),
+            None => {
+                let cancelled = inner.is_aborted();
+                // Dropping the body closes the connection.
+                self.inner = None;
+                Poll::Ready(cancelled.then_some(Err(LlmError::Cancelled)))
+            }
+        }
+    }
+}
+
+impl ChatStream {
+    /// Interleave [`StreamEvent::ToolCallPartial`] snapshots after each tool
+    /// argument delta and [`StreamEvent::ToolCallComplete`] when a tool call
+    /// block stops. See [`ToolCallTracker`].
+    pub fn with_tool_call_snapshots(self) -> ChatStream {
+        let mut tracker = ToolCallTracker::default();
+        ChatStream::new(self.flat_map(move |item| {
+            let items = match item {
+                Ok(event) => {
+                    let derived = tracker.push(&event);
+                    std::iter::once(Ok(event)).chain(derived).collect()
+                }
+                Err(error) => vec![Err(error)],
+            };
+            stream::iter(items)
+        }))
+    }
+}
+
+/// Derives parsed views of streamed tool arguments from
+/// [`StreamEvent::ToolCallDelta`] events.
+#[derive(Debug, Default)]
+pub struct ToolCallTracker {
+    /// Block `index` → (tool name, raw arguments, last emitted snapshot).
+    calls: BTreeMap<usize, (String, String, Option<serde_json::Value>)>,
+}
+
+impl ToolCallTracker {
+    /// Derived events for `event`: a `ToolCallPartial` when the parsed
+    /// arguments changed, a `ToolCallComplete` (or an error for invalid
+    /// input) when a tool call block stops.
+    pub fn push(&mut self, event: &StreamEvent) -> Vec<Result<StreamEvent, LlmError>> {
+        match event {
+            StreamEvent::BlockStart {
+                index,
+                kind: BlockKind::ToolUse { name, .. } | BlockKind::ServerToolUse { name, .. },
+            } => {
+                self.calls
+                    .insert(*index, (name.clone(), String::new(), None));
+                Vec::new()
+            }
+            StreamEvent::ToolCallDelta { index, arguments } => {
+                let (_, buffer, last) = self.calls.entry(*index).or_default();
+                buffer.push_str(arguments);
+                match parse_partial_json(buffer) {
+                    Some(value) if last.as_ref() != Some(&value) => {
+                        *last = Some(value.clone());
+                        vec![Ok(StreamEvent::ToolCallPartial {
+                            index: *index,
+                            value,
+                        })]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            StreamEvent::BlockStop { index } => {
+                let Some((name, arguments, _)) = self.calls.remove(index) else {
+                    return Vec::new();
+                };
+                let input = if arguments.trim().is_empty() {
+                    Ok(serde_json::json!({}))
+                } else {
+                    serde_json::from_str::<serde_json::Value>(&arguments)
+                        .map_err(|error| error.to_string())
+                        .and_then(|input| {
+                            if input.is_object() {
+                                Ok(input)
+                            } else {
+                                Err("expected a JSON object".into())
+                            }
+                        })
+                };
+                vec![
+                    input
+                        .map(|input| StreamEvent::ToolCallComplete {
+                            index: *index,
+                            input,
+                        })
+                        .map_err(|error| {
+                            LlmError::Stream(format!("invalid arguments for tool {name}: {error}"))
+                        }),
+                ]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Cancels a [`ChatStream`] from another task. The stream yields
+/// [`LlmError::Cancelled`] and then ends.
+#[derive(Debug, Clone)]
+pub struct CancelHandle(AbortHandle);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.abort();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_aborted()
+    }
 }
 
 impl LlmClient {
     /// Stream a chat completion and normalize provider SSE events.
     ///
     /// The returned stream intentionally exposes tool argument deltas instead
-    /// of buffering them. Callers that execute tools should accumulate deltas
-    /// by `index`, then parse the completed JSON on `ToolCallComplete`/`Done`.
+    /// of buffering them. Wrap it in [`AccumulatingStream`] to also get the
+    /// complete [`MessagesResponse`] once the stream ends.
+    ///
+    /// The initial request is retried like [`LlmClient::chat`]. With
+    /// [`RetryPolicy::restart_stream_before_content`] set, a stream that fails
+    /// before its first content delta is restarted as well.
+    ///
+    /// [`RetryPolicy::restart_stream_before_content`]: super::RetryPolicy::restart_stream_before_content
     pub async fn chat_stream(
         &self,
         messages: &[Message],
         options: ChatOptions<'_>,
     ) -> Result<ChatStream, LlmError> {
+        let messages = &*self.fit_context(messages, &options).await?;
         let (url, body) = match self.config.provider {
             Provider::OpenAiCompatible => {
-                let request = ChatRequest {
-                    model: self.config.model.clone(),
-                    max_tokens: Some(self.config.max_tokens),
-                    messages: to_openai::messages_to_openai(options.system, messages),
-                    temperature: options.temperature,
-                    tools: options.tools.map(to_openai::tools_to_openai),
-                    response_format: options.response_format.cloned(),
-                    tool_choice: options.required_tool.map(
-                        |name| serde_json::json!({"type": "function", "function": {"name": name}}),
-                    ),
-                };
+                let request = self.openai_request(messages, &options)?;
                 let mut value = serde_json::to_value(request)
                     .map_err(|error| LlmError::Client(error.to_string()))?;
                 value["stream"] = serde_json::json!(true);
@@ -70,19 +201,7 @@ impl LlmClient {
                 (self.endpoint("chat/completions"), value)
             }
             Provider::Anthropic => {
-                let (thinking, output_config) = build_thinking_params(options.thinking);
-                let request = MessagesRequest {
-                    model: self.config.model.clone(),
-                    max_tokens: self.config.max_tokens,
-                    system: options.system.map(str::to_string),
-                    messages: messages.to_vec(),
-                    tools: options.tools.map(<[_]>::to_vec),
-                    thinking,
-                    output_config,
-                    tool_choice: options
-                        .required_tool
-                        .map(|name| serde_json::json!({"type": "tool", "name": name})),
-                };
+                let request = self.anthropic_request(messages, &options)?;
                 let mut value = serde_json::to_value(request)
                     .map_err(|error| LlmError::Client(error.to_string()))?;
                 value["stream"] = serde_json::json!(true);
@@ -90,270 +209,801 @@ impl LlmClient {
             }
         };
 
-        let response = self.request(&url)?.json(&body).send().await?;
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            return Err(LlmError::ApiError {
-                status: status.as_u16(),
-                body,
-            });
+        let body =
+            serde_json::to_vec(&body).map_err(|error| LlmError::Client(error.to_string()))?;
+        let mut attempt = 0;
+        let events = self
+            .open_stream(&url, &body, options.timeout, &mut attempt)
+            .await?;
+        if !self.config.retry_policy.restart_stream_before_content {
+            return Ok(ChatStream::new(events));
         }
 
-        let provider = self.config.provider;
-        let events = response
+        let restart = Restart {
+            client: self.clone(),
+            url,
+            body,
+            timeout: options.timeout,
+            attempt,
+            events,
+            held: Vec::new(),
+            released: false,
+        };
+        let events = stream::unfold(Some(restart), |state| async move {
+            let mut state = state?;
+            loop {
+                let next = state.events.next().await;
+                if state.released {
+                    return next.map(|item| (vec![item], Some(state)));
+                }
+                match next {
+                    Some(Ok(event)) if is_preamble(&event) => state.held.push(event),
+                    Some(Ok(event)) => {
+                        state.released = true;
+                        let mut items: Vec<_> = state.held.drain(..).map(Ok).collect();
+                        items.push(Ok(event));
+                        return Some((items, Some(state)));
+                    }
+                    Some(Err(error))
+                        if error.is_retryable_under(&state.client.config.retry_policy)
+                            && state.attempt < state.client.config.retry_policy.max_retries =>
+                    {
+                        let delay = state.client.config.retry_policy.delay(state.attempt, None);
+                        state.attempt += 1;
+                        tracing::warn!(%error, attempt = state.attempt, ?delay, "restarting LLM stream");
+                        tokio::time::sleep(delay).await;
+                        state.held.clear();
+                        let Restart {
+                            client,
+                            url,
+                            body,
+                            timeout,
+                            attempt,
+                            ..
+                        } = &mut state;
+                        match client.open_stream(url, body, *timeout, attempt).await {
+                            Ok(events) => state.events = events,
+                            Err(error) => return Some((vec![Err(error)], None)),
+                        }
+                    }
+                    Some(Err(error)) => {
+                        state.released = true;
+                        let mut items: Vec<_> = state.held.drain(..).map(Ok).collect();
+                        items.push(Err(error));
+                        return Some((items, Some(state)));
+                    }
+                    None => {
+                        state.released = true;
+                        let items: Vec<_> = state.held.drain(..).map(Ok).collect();
+                        return Some((items, Some(state)));
+                    }
+                }
+            }
+        })
+        .flat_map(stream::iter);
+        Ok(ChatStream::new(events))
+    }
+
+    /// Send the streaming request (retrying the connection and retryable
+    /// statuses) and decode its body into events.
+    async fn open_stream(
+        &self,
+        url: &str,
+        body: &[u8],
+        timeout: Option<Duration>,
+        attempt: &mut u32,
+    ) -> Result<BoxedEvents, LlmError> {
+        let response = self.send_checked(url, Some(body), timeout, attempt).await?;
+        let limit = self.config.max_response_bytes;
+        let mut received = 0usize;
+        let source = response
             .bytes_stream()
-            .eventsource()
-            .map(move |event| match event {
-                Ok(event) => match provider {
-                    Provider::OpenAiCompatible => parse_openai_event(&event.data),
-                    Provider::Anthropic => parse_anthropic_event(&event.event, &event.data),
-                },
-                Err(error) => vec![Err(LlmError::Stream(error.to_string()))],
+            .map(move |chunk| {
+                let chunk = chunk?;
+                received += chunk.len();
+                if received > limit {
+                    return Err(LlmError::ResponseTooLarge {
+                        limit,
+                        actual: received,
+                    });
+                }
+                Ok(chunk)
             })
-            .flat_map(stream::iter);
+            .eventsource();
+        let decoder = StreamDecoder::new(self.config.provider);
+        let idle_timeout = self.config.stream_idle_timeout;
+        let events = stream::unfold(Some((Box::pin(source), decoder)), move |state| async move {
+            let (mut source, mut decoder) = state?;
+            let next = match idle_timeout {
+                Some(idle) => match tokio::time::timeout(idle, source.next()).await {
+                    Ok(next) => next,
+                    Err(_) => return Some((vec![Err(LlmError::StreamIdleTimeout(idle))], None)),
+                },
+                None => source.next().await,
+            };
+            match next {
+                Some(Ok(event)) => {
+                    let events = match decoder.decode(&event.event, &event.data) {
+                        Ok(events) => events.into_iter().map(into_result).collect(),
+                        Err(error) => vec![Err(LlmError::Stream(error))],
+                    };
+                    Some((events, Some((source, decoder))))
+                }
+                Some(Err(EventStreamError::Transport(error))) => Some((vec![Err(error)], None)),
+                Some(Err(error)) => Some((vec![Err(LlmError::Stream(error.to_string()))], None)),
+                None => Some((decoder.finish().into_iter().map(Ok).collect(), None)),
+            }
+        })
+        .flat_map(stream::iter);
         Ok(Box::pin(events))
     }
 }
 
-fn parse_openai_event(data: &str) -> Vec<Result<StreamEvent, LlmError>> {
-    if data.trim() == "[DONE]" {
-        return vec![Ok(StreamEvent::Done { stop_reason: None })];
+/// Item of [`LlmClient::complete_structured_stream`].
+#[derive(Debug, Clone)]
+pub enum StructuredEvent<T> {
+    /// Best-effort parse of the output so far; changes on every snapshot.
+    Partial(serde_json::Value),
+    /// The deserialized result, always the last item.
+    Done(StructuredResponse<T>),
+}
+
+pub type StructuredStream<T> =
+    Pin<Box<dyn Stream<Item = Result<StructuredEvent<T>, LlmError>> + Send>>;
+
+impl LlmClient {
+    /// Streaming variant of [`complete_structured_with`]: yields partial
+    /// JSON snapshots as the output arrives, then the deserialized `T`.
+    ///
+    /// [`complete_structured_with`]: LlmClient::complete_structured_with
+    pub async fn complete_structured_stream<T>(
+        &self,
+        user: &str,
+        schema_name: &str,
+        options: ChatOptions<'_>,
+    ) -> Result<StructuredStream<T>, LlmError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + 'static,
+    {
+        let provider = self.config.provider;
+        let plumbing = StructuredPlumbing::new::<T>(provider, schema_name)?;
+        let events = self
+            .chat_stream(&[Message::user_text(user)], plumbing.options(options))
+            .await?;
+
+        let state = StructuredState {
+            events,
+            accumulator: StreamAccumulator::new(),
+            output: String::new(),
+            snapshot: None,
+            plumbing,
+        };
+        let items = stream::unfold(Some(state), move |state| async move {
+            let mut state = state?;
+            loop {
+                let event = match state.events.next().await {
+                    Some(Ok(event)) => event,
+                    Some(Err(error)) => return Some((Err(error), None)),
+                    None => {
+                        let result = state.accumulator.finish().and_then(|response| {
+                            Ok(StructuredEvent::Done(StructuredResponse {
+                                data: state.plumbing.parse(&response)?,
+                                usage: response.usage.unwrap_or_default(),
+                            }))
+                        });
+                        return Some((result, None));
+                    }
+                };
+                state.accumulator.push(&event);
+                match (provider, event) {
+                    (Provider::OpenAiCompatible, StreamEvent::TextDelta { text, .. })
+                    | (
+                        Provider::Anthropic,
+                        StreamEvent::ToolCallDelta {
+                            arguments: text, ..
+                        },
+                    ) => state.output.push_str(&text),
+                    _ => continue,
+                }
+                if let Some(value) = parse_partial_json(&state.output)
+                    && state.snapshot.as_ref() != Some(&value)
+                {
+                    state.snapshot = Some(value.clone());
+                    return Some((Ok(StructuredEvent::Partial(value)), Some(state)));
+                }
+            }
+        });
+        Ok(Box::pin(items))
     }
-    let value: serde_json::Value = match serde_json::from_str(data) {
-        Ok(value) => value,
-        Err(error) => return vec![Err(LlmError::Stream(error.to_string()))],
-    };
-    let mut events = Vec::new();
-    if let Some(usage) = value.get("usage").filter(|usage| !usage.is_null()) {
-        let input_tokens = usage
-            .get("prompt_tokens")
-            .and_then(serde_json::Value::as_u64)
-            .unwrap_or_default();
-        let output_tokens = usage
-            .get("completion_tokens")
-            .and_then(serde_json::Value::as_u64)
-            .unwrap_or_default();
-        events.push(Ok(StreamEvent::Usage {
-            usage: Usage {
-                input_tokens,
-                output_tokens,
-                cache_creation_input_tokens: usage
-                    .pointer("/prompt_tokens_details/cache_write_tokens")
-                    .and_then(serde_json::Value::as_u64),
-                cache_read_input_tokens: usage
-                    .pointer("/prompt_tokens_details/cached_tokens")
-                    .and_then(serde_json::Value::as_u64),
-                reasoning_tokens: usage
-                    .pointer("/completion_tokens_details/reasoning_tokens")
-                    .and_then(serde_json::Value::as_u64)
-                    .unwrap_or_default(),
-                cost: usage.get("cost").and_then(serde_json::Value::as_f64),
-            },
-        }));
+}
+
+struct StructuredState {
+    events: ChatStream,
+    accumulator: StreamAccumulator,
+    /// Raw JSON text received so far.
+    output: String,
+    snapshot: Option<serde_json::Value>,
+    plumbing: StructuredPlumbing,
+}
+
+/// State of a stream that may be restarted before its first content delta.
+struct Restart {
+    client: LlmClient,
+    url: String,
+    body: Vec<u8>,
+    timeout: Option<Duration>,
+    attempt: u32,
+    events: BoxedEvents,
+    /// Events received before the first content delta.
+    held: Vec<StreamEvent>,
+    released: bool,
+}
+
+/// Events that carry no model output and can be replayed by a restart.
+fn is_preamble(event: &StreamEvent) -> bool {
+    matches!(
+        event,
+        StreamEvent::MessageStart { .. }
+            | StreamEvent::Usage { .. }
+            | StreamEvent::Ping
+            | StreamEvent::BlockStart {
+                kind: BlockKind::Text
+                    | BlockKind::Thinking
+                    | BlockKind::ToolUse { .. }
+                    | BlockKind::ServerToolUse { .. },
+                ..
+            }
+    )
+}
+
+/// Provider errors inside the stream surface as [`LlmError::ApiError`] so
+/// callers can use [`LlmError::is_retryable`].
+fn into_result(event: StreamEvent) -> Result<StreamEvent, LlmError> {
+    match event {
+        StreamEvent::Error {
+            error_type,
+            message,
+        } => Err(LlmError::api(
+            200,
+            serde_json::json!({
+                "type": "error",
+                "error": { "type": error_type, "message": message }
+            })
+            .to_string(),
+            None,
+        )),
+        event => Ok(event),
     }
-    for choice in value
-        .get("choices")
-        .and_then(serde_json::Value::as_array)
-        .into_iter()
-        .flatten()
-    {
-        let delta = choice.get("delta").unwrap_or(&serde_json::Value::Null);
-        if let Some(text) = delta.get("content").and_then(serde_json::Value::as_str)
-            && !text.is_empty()
-        {
-            events.push(Ok(StreamEvent::TextDelta { text: text.into() }));
-        }
-        if let Some(text) = delta
-            .get("reasoning_content")
-            .or_else(|| delta.get("reasoning"))
-            .and_then(serde_json::Value::as_str)
-            && !text.is_empty()
-        {
-            events.push(Ok(StreamEvent::ThinkingDelta { text: text.into() }));
+}
+
+/// Builds a [`MessagesResponse`] from [`StreamEvent`]s.
+///
+/// Deltas are applied to the block at their `index`; tool arguments are
+/// buffered and parsed in [`finish`].
+///
+/// [`finish`]: StreamAccumulator::finish
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    id: Option<String>,
+    model: Option<String>,
+    content: Vec<ContentBlock>,
+    /// Stream block `index` → position in `content`.
+    positions: BTreeMap<usize, usize>,
+    /// Position in `content` → raw tool argument JSON.
+    arguments: BTreeMap<usize, String>,
+    usage: Option<Usage>,
+    stop_reason: Option<StopReason>,
+    done: bool,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::MessageStart { id, model } => {
+                self.id.clone_from(id);
+                self.model.clone_from(model);
+            }
+            StreamEvent::BlockStart { index, kind } => {
+                let block = match kind {
+                    BlockKind::Text => ContentBlock::text(""),
+                    BlockKind::Thinking => ContentBlock::thinking("", None),
+                    BlockKind::RedactedThinking { data } => ContentBlock::RedactedThinking {
+                        data: data.clone(),
+                        extra: serde_json::Map::new(),
+                    },
+                    BlockKind::ToolUse { id, name } => {
+                        ContentBlock::tool_use(id.clone(), name.clone(), serde_json::Value::Null)
+                    }
+                    BlockKind::ServerToolUse { id, name } => ContentBlock::ServerToolUse {
+                        id: id.clone(),
+                        name: name.clone(),
+                        input: serde_json::Value::Null,
+                        extra: serde_json::Map::new(),
+                    },
+                    BlockKind::Other { block } => serde_json::from_value(block.clone())
+                        .unwrap_or_else(|_| ContentBlock::Unknown(block.clone())),
+                };
+                self.positions.insert(*index, self.content.len());
+                self.content.push(block);
+            }
+            StreamEvent::TextDelta { index, text } => {
+                if let ContentBlock::Text { text: current, .. } =
+                    self.block(*index, || ContentBlock::text(""))
+                {
+                    current.push_str(text);
+                }
+            }
+            StreamEvent::ThinkingDelta { index, text } => {
+                if let ContentBlock::Thinking { thinking, .. } =
+                    self.block(*index, || ContentBlock::thinking("", None))
+                {
+                    thinking.push_str(text);
+                }
+            }
+            StreamEvent::ThinkingSignature { index, signature } => {
+                if let ContentBlock::Thinking {
+                    signature: current, ..
+                } = self.block(*index, || ContentBlock::thinking("", None))
+                {
+                    current.get_or_insert_default().push_str(signature);
+                }
+            }
+            StreamEvent::ToolCallDelta { index, arguments } => {
+                self.block(*index, || {
+                    ContentBlock::tool_use("", "", serde_json::Value::Null)
+                });
+                let position = self.positions[index];
+                self.arguments
+                    .entry(position)
+                    .or_default()
+                    .push_str(arguments);
+            }
+            StreamEvent::Usage { usage } => {
+                self.usage.get_or_insert_default().merge(usage);
+            }
+            StreamEvent::Done { stop_reason } => {
+                self.done = true;
+                if stop_reason.is_some() {
+                    self.stop_reason.clone_from(stop_reason);
+                }
+            }
+            StreamEvent::BlockStop { .. }
+            | StreamEvent::ToolCallPartial { .. }
+            | StreamEvent::ToolCallComplete { .. }
+            | StreamEvent::Ping
+            | StreamEvent::Error { .. } => {}
         }
-        for call in delta
-            .get("tool_calls")
-            .and_then(serde_json::Value::as_array)
-            .into_iter()
-            .flatten()
-        {
-            let index = call
-                .get("index")
-                .and_then(serde_json::Value::as_u64)
-                .unwrap_or_default() as usize;
-            events.push(Ok(StreamEvent::ToolCallDelta {
-                index,
-                id: call
-                    .get("id")
-                    .and_then(serde_json::Value::as_str)
-                    .map(str::to_string),
-                name: call
-                    .pointer("/function/name")
-                    .and_then(serde_json::Value::as_str)
-                    .map(str::to_string),
-                arguments: call
-                    .pointer("/function/arguments")
-                    .and_then(serde_json::Value::as_str)
-                    .unwrap_or_default()
-                    .to_string(),
-            }));
+    }
+
+    /// Block at stream `index`, created with `default` if no `BlockStart`
+    /// was seen for it.
+    fn block(&mut self, index: usize, default: impl FnOnce() -> ContentBlock) -> &mut ContentBlock {
+        let position = *self.positions.entry(index).or_insert_with(|| {
+            self.content.push(default());
+            self.content.len() - 1
+        });
+        &mut self.content[position]
+    }
+
+    /// The assembled response. Fails if the stream never reached `Done` or
+    /// a tool call's arguments are not valid JSON.
+    pub fn finish(self) -> Result<MessagesResponse, LlmError> {
+        if !self.done {
+            return Err(LlmError::Stream("stream ended before completion".into()));
         }
-        if let Some(reason) = choice
-            .get("finish_reason")
-            .and_then(serde_json::Value::as_str)
-        {
-            events.push(Ok(StreamEvent::Done {
-                stop_reason: Some(reason.into()),
-            }));
+        let mut content = self.content;
+        for (position, block) in content.iter_mut().enumerate() {
+            let (ContentBlock::ToolUse { name, input, .. }
+            | ContentBlock::ServerToolUse { name, input, .. }) = block
+            else {
+                continue;
+            };
+            let arguments = self.arguments.get(&position).map_or("", String::as_str);
+            *input = if arguments.trim().is_empty() {
+                serde_json::json!({})
+            } else {
+                serde_json::from_str(arguments).map_err(|error| {
+                    LlmError::Stream(format!("invalid arguments for tool {name}: {error}"))
+                })?
+            };
         }
+        let stop_reason = self.stop_reason.unwrap_or_else(|| {
+            if content
+                .iter()
+                .any(|block| matches!(block, ContentBlock::ToolUse { .. }))
+            {
+                StopReason::ToolUse
+            } else {
+                StopReason::EndTurn
+            }
+        });
+        Ok(MessagesResponse {
+            id: self.id,
+            model: self.model,
+            content,
+            stop_reason,
+            usage: self.usage,
+        })
     }
-    events
 }
 
-fn parse_anthropic_event(event: &str, data: &str) -> Vec<Result<StreamEvent, LlmError>> {
-    let value: serde_json::Value = match serde_json::from_str(data) {
-        Ok(value) => value,
-        Err(error) => return vec![Err(LlmError::Stream(error.to_string()))],
-    };
-    let parsed = match event {
-        "content_block_delta" => match value
-            .pointer("/delta/type")
-            .and_then(serde_json::Value::as_str)
-        {
-            Some("text_delta") => value
-                .pointer("/delta/text")
-                .and_then(serde_json::Value::as_str)
-                .map(|text| StreamEvent::TextDelta { text: text.into() }),
-            Some("thinking_delta") => value
-                .pointer("/delta/thinking")
-                .and_then(serde_json::Value::as_str)
-                .map(|text| StreamEvent::ThinkingDelta { text: text.into() }),
-            Some("input_json_delta") => Some(StreamEvent::ToolCallDelta {
-                index: value
-                    .get("index")
-                    .and_then(serde_json::Value::as_u64)
-                    .unwrap_or_default() as usize,
-                id: None,
-                name: None,
-                arguments: value
-                    .pointer("/delta/partial_json")
-                    .and_then(serde_json::Value::as_str)
-                    .unwrap_or_default()
-                    .into(),
-            }),
-            _ => None,
-        },
-        "content_block_start"
-            if value
-                .pointer("/content_block/type")
-                .and_then(serde_json::Value::as_str)
-                == Some("tool_use") =>
-        {
-            Some(StreamEvent::ToolCallDelta {
-                index: value
-                    .get("index")
-                    .and_then(serde_json::Value::as_u64)
-                    .unwrap_or_default() as usize,
-                id: value
-                    .pointer("/content_block/id")
-                    .and_then(serde_json::Value::as_str)
-                    .map(str::to_string),
-                name: value
-                    .pointer("/content_block/name")
-                    .and_then(serde_json::Value::as_str)
-                    .map(str::to_string),
-                arguments: String::new(),
-            })
-        }
-        "content_block_stop" => Some(StreamEvent::ToolCallComplete {
-            index: value
-                .get("index")
-                .and_then(serde_json::Value::as_u64)
-                .unwrap_or_default() as usize,
-        }),
-        "message_start" => value
-            .pointer("/message/usage")
-            .map(|usage| StreamEvent::Usage {
-                usage: anthropic_usage(usage),
-            }),
-        "message_delta" => {
-            let usage = value.get("usage").map(anthropic_usage);
-            let reason = value
-                .pointer("/delta/stop_reason")
-                .and_then(serde_json::Value::as_str)
-                .map(str::to_string);
-            if let Some(usage) = usage {
-                return vec![
-                    Ok(StreamEvent::Usage { usage }),
-                    Ok(StreamEvent::Done {
-                        stop_reason: reason,
-                    }),
-                ];
-            }
-            Some(StreamEvent::Done {
-                stop_reason: reason,
-            })
+/// Passes [`StreamEvent`]s through unchanged while accumulating them into a
+/// [`MessagesResponse`].
+pub struct AccumulatingStream {
+    inner: ChatStream,
+    accumulator: StreamAccumulator,
+}
+
+impl AccumulatingStream {
+    pub fn new(inner: ChatStream) -> Self {
+        Self {
+            inner,
+            accumulator: StreamAccumulator::new(),
         }
-        "message_stop" => Some(StreamEvent::Done { stop_reason: None }),
-        "error" => {
-            return vec![Err(LlmError::Stream(
-                value
-                    .pointer("/error/message")
-                    .and_then(serde_json::Value::as_str)
-                    .unwrap_or("Anthropic stream error")
-                    .into(),
-            ))];
+    }
+
+    pub fn accumulator(&self) -> &StreamAccumulator {
+        &self.accumulator
+    }
+
+    /// Finish after the stream has been drained. See [`StreamAccumulator::finish`].
+    pub fn finish(self) -> Result<MessagesResponse, LlmError> {
+        self.accumulator.finish()
+    }
+
+    /// Drain the remaining events and return the complete response.
+    pub async fn collect_response(mut self) -> Result<MessagesResponse, LlmError> {
+        while let Some(event) = self.next().await {
+            event?;
         }
-        _ => None,
-    };
-    parsed.into_iter().map(Ok).collect()
+        self.finish()
+    }
 }
 
-fn anthropic_usage(value: &serde_json::Value) -> Usage {
-    Usage {
-        input_tokens: value
-            .get("input_tokens")
-            .and_then(serde_json::Value::as_u64)
-            .unwrap_or_default(),
-        output_tokens: value
-            .get("output_tokens")
-            .and_then(serde_json::Value::as_u64)
-            .unwrap_or_default(),
-        cache_creation_input_tokens: value
-            .get("cache_creation_input_tokens")
-            .and_then(serde_json::Value::as_u64),
-        cache_read_input_tokens: value
-            .get("cache_read_input_tokens")
-            .and_then(serde_json::Value::as_u64),
-        reasoning_tokens: 0,
-        cost: None,
+impl Stream for AccumulatingStream {
+    type Item = Result<StreamEvent, LlmError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = std::task::ready!(self.inner.poll_next_unpin(cx));
+        if let Some(Ok(event)) = &item {
+            self.accumulator.push(event);
+        }
+        Poll::Ready(item)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::test_server::{Reply, recording_server, sse_response};
+    use crate::types::common::Provider;
 
     #[test]
-    fn parses_openai_text_tools_and_usage() {
-        let events = parse_openai_event(
-            r#"{"choices":[{"delta":{"content":"hi","tool_calls":[{"index":0,"id":"call_1","function":{"name":"search","arguments":"{}"}}]},"finish_reason":null}],"usage":{"prompt_tokens":3,"completion_tokens":1,"total_tokens":4}}"#,
+    fn accumulates_anthropic_events_into_response() {
+        let events = [
+            (
+                "message_start",
+                r#"{"message":{"id":"msg_1","model":"claude","usage":{"input_tokens":12,"output_tokens":1}}}"#,
+            ),
+            (
+                "content_block_start",
+                r#"{"index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+            ),
+            (
+                "content_block_delta",
+                r#"{"index":0,"delta":{"type":"thinking_delta","thinking":"Let me "}}"#,
+            ),
+            (
+                "content_block_delta",
+                r#"{"index":0,"delta":{"type":"thinking_delta","thinking":"check."}}"#,
+            ),
+            (
+                "content_block_delta",
+                r#"{"index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#,
+            ),
+            ("content_block_stop", r#"{"index":0}"#),
+            (
+                "content_block_start",
+                r#"{"index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"search","input":{}}}"#,
+            ),
+            (
+                "content_block_delta",
+                r#"{"index":1,"delta":{"type":"input_json_delta","partial_json":"{\"q\":"}}"#,
+            ),
+            (
+                "content_block_delta",
+                r#"{"index":1,"delta":{"type":"input_json_delta","partial_json":"\"rust\"}"}}"#,
+            ),
+            ("content_block_stop", r#"{"index":1}"#),
+            (
+                "message_delta",
+                r#"{"delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":30}}"#,
+            ),
+            ("message_stop", r#"{}"#),
+        ];
+        let mut decoder = StreamDecoder::new(Provider::Anthropic);
+        let mut accumulator = StreamAccumulator::new();
+        for (event, data) in events {
+            for event in decoder.decode(event, data).unwrap() {
+                accumulator.push(&event);
+            }
+        }
+        let response = accumulator.finish().unwrap();
+        assert_eq!(response.id.as_deref(), Some("msg_1"));
+        assert_eq!(
+            serde_json::to_value(&response.content).unwrap(),
+            serde_json::json!([
+                { "type": "thinking", "thinking": "Let me check.", "signature": "sig" },
+                { "type": "tool_use", "id": "toolu_1", "name": "search", "input": { "q": "rust" } }
+            ])
         );
-        assert!(matches!(events[0], Ok(StreamEvent::Usage { .. })));
-        assert!(matches!(events[1], Ok(StreamEvent::TextDelta { .. })));
-        assert!(matches!(events[2], Ok(StreamEvent::ToolCallDelta { .. })));
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (12, 30));
+    }
+
+    #[test]
+    fn surfaces_in_stream_provider_errors_as_api_errors() {
+        let error = into_result(StreamEvent::Error {
+            error_type: "overloaded_error".into(),
+            message: "Overloaded".into(),
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            LlmError::ApiError {
+                kind: crate::client::ApiErrorKind::Overloaded,
+                ..
+            }
+        ));
+        assert!(error.is_retryable());
     }
 
     #[test]
-    fn parses_anthropic_tool_input_delta() {
-        let events = parse_anthropic_event(
-            "content_block_delta",
-            r#"{"index":1,"delta":{"type":"input_json_delta","partial_json":"{\"q\":\"rust\"}"}}"#,
+    fn tracks_partial_and_complete_tool_arguments() {
+        let mut tracker = ToolCallTracker::default();
+        let mut derived = Vec::new();
+        for event in [
+            StreamEvent::BlockStart {
+                index: 1,
+                kind: BlockKind::ToolUse {
+                    id: "toolu_1".into(),
+                    name: "search".into(),
+                },
+            },
+            StreamEvent::ToolCallDelta {
+                index: 1,
+                arguments: r#"{"query":"ru"#.into(),
+            },
+            StreamEvent::ToolCallDelta {
+                index: 1,
+                arguments: r#"st","#.into(),
+            },
+            StreamEvent::ToolCallDelta {
+                index: 1,
+                arguments: r#""limit":5}"#.into(),
+            },
+            StreamEvent::BlockStop { index: 1 },
+        ] {
+            derived.extend(tracker.push(&event).into_iter().map(Result::unwrap));
+        }
+        assert_eq!(
+            derived,
+            [
+                StreamEvent::ToolCallPartial {
+                    index: 1,
+                    value: serde_json::json!({"query": "ru"})
+                },
+                StreamEvent::ToolCallPartial {
+                    index: 1,
+                    value: serde_json::json!({"query": "rust"})
+                },
+                StreamEvent::ToolCallPartial {
+                    index: 1,
+                    value: serde_json::json!({"query": "rust", "limit": 5})
+                },
+                StreamEvent::ToolCallComplete {
+                    index: 1,
+                    input: serde_json::json!({"query": "rust", "limit": 5})
+                },
+            ]
         );
+
+        tracker.push(&StreamEvent::ToolCallDelta {
+            index: 2,
+            arguments: "[1,".into(),
+        });
+        assert!(matches!(
+            tracker.push(&StreamEvent::BlockStop { index: 2 })[..],
+            [Err(LlmError::Stream(_))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn enforces_idle_timeout_byte_limit_and_cancellation() {
+        use std::time::Duration;
+
+        use crate::client::ClientConfig;
+
+        let message = [Message::user_text("hi")];
+        let body = "event: ping\ndata: {\"type\":\"ping\"}\n\n";
+
+        let (url, _) = recording_server(vec![Reply::Stall(sse_response(body))]).await;
+        let client = LlmClient::new(
+            ClientConfig::anthropic("", "model")
+                .base_url(url)
+                .stream_idle_timeout(Duration::from_millis(50)),
+        )
+        .unwrap();
+        let mut stream = client
+            .chat_stream(&message, ChatOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(StreamEvent::Ping))));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(LlmError::StreamIdleTimeout(_)))
+        ));
+        assert!(stream.next().await.is_none());
+
+        let (url, _) = recording_server(vec![Reply::Stall(sse_response(body))]).await;
+        let client = LlmClient::new(
+            ClientConfig::anthropic("", "model")
+                .base_url(url)
+                .max_response_bytes(8),
+        )
+        .unwrap();
+        let mut stream = client
+            .chat_stream(&message, ChatOptions::default())
+            .await
+            .unwrap();
         assert!(matches!(
-            events[0],
-            Ok(StreamEvent::ToolCallDelta { index: 1, .. })
+            stream.next().await,
+            Some(Err(LlmError::ResponseTooLarge { limit: 8, .. }))
         ));
+
+        let (url, _) = recording_server(vec![Reply::Stall(sse_response(body))]).await;
+        let client = LlmClient::new(ClientConfig::anthropic("", "model").base_url(url)).unwrap();
+        let mut stream = client
+            .chat_stream(&message, ChatOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(StreamEvent::Ping))));
+        let handle = stream.cancel_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            handle.cancel();
+        });
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(LlmError::Cancelled))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn streams_partial_structured_output() {
+        #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+        struct Person {
+            name: String,
+            age: u32,
+        }
+
+        let (url, _) = recording_server(vec![sse_response(
+            "event: content_block_start\ndata: {\"index\":0,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"person\",\"input\":{}}}\n\n\
+             event: content_block_delta\ndata: {\"index\":0,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"name\\\":\\\"Ad\"}}\n\n\
+             event: content_block_delta\ndata: {\"index\":0,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"a\\\",\\\"age\\\":36}\"}}\n\n\
+             event: content_block_stop\ndata: {\"index\":0}\n\n\
+             event: message_delta\ndata: {\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":7}}\n\n\
+             event: message_stop\ndata: {}\n\n",
+        )])
+        .await;
+        let client =
+            LlmClient::new(crate::client::ClientConfig::anthropic("", "model").base_url(url))
+                .unwrap();
+        let items: Vec<_> = client
+            .complete_structured_stream::<Person>("who?", "person", ChatOptions::default())
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert!(matches!(
+            &items[0],
+            StructuredEvent::Partial(value) if *value == serde_json::json!({"name": "Ad"})
+        ));
+        let Some(StructuredEvent::Done(response)) = items.last() else {
+            panic!("missing final result: {items:?}");
+        };
+        assert_eq!(response.data.name, "Ada");
+        assert_eq!(response.data.age, 36);
+        assert_eq!(response.usage.output_tokens, 7);
+    }
+
+    #[tokio::test]
+    async fn does_not_restart_after_idle_timeout_unless_enabled() {
+        use crate::client::{ClientConfig, RetryPolicy};
+
+        let ping = "event: ping\ndata: {\"type\":\"ping\"}\n\n";
+        let (url, mut requests) = recording_server(vec![
+            Reply::Stall(sse_response(ping)),
+            Reply::Stall(sse_response(ping)),
+        ])
+        .await;
+        let client = LlmClient::new(
+            ClientConfig::anthropic("", "model")
+                .base_url(url)
+                .stream_idle_timeout(Duration::from_millis(50))
+                .retry_policy(RetryPolicy {
+                    initial_backoff: Duration::from_millis(1),
+                    restart_stream_before_content: true,
+                    ..RetryPolicy::default()
+                }),
+        )
+        .unwrap();
+        let events: Vec<_> = client
+            .chat_stream(&[Message::user_text("hi")], ChatOptions::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(matches!(events[0], Ok(StreamEvent::Ping)));
+        assert!(matches!(events[1], Err(LlmError::StreamIdleTimeout(_))));
+        assert!(requests.recv().await.is_some());
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn restarts_stream_that_fails_before_content() {
+        use crate::client::{ClientConfig, RetryPolicy};
+
+        let start =
+            "event: message_start\ndata: {\"message\":{\"id\":\"msg_1\",\"model\":\"claude\"}}\n\n";
+        let (url, _) = recording_server(vec![
+            "HTTP/1.1 529 Overloaded\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string(),
+            sse_response(&format!(
+                "{start}event: error\ndata: {{\"type\":\"error\",\"error\":{{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}}}\n\n"
+            )),
+            sse_response(&format!(
+                "{start}event: content_block_start\ndata: {{\"index\":0,\"content_block\":{{\"type\":\"text\",\"text\":\"\"}}}}\n\n\
+                 event: content_block_delta\ndata: {{\"index\":0,\"delta\":{{\"type\":\"text_delta\",\"text\":\"hi\"}}}}\n\n\
+                 event: message_delta\ndata: {{\"delta\":{{\"stop_reason\":\"end_turn\"}}}}\n\n\
+                 event: message_stop\ndata: {{}}\n\n"
+            )),
+        ])
+        .await;
+
+        let client = LlmClient::new(
+            ClientConfig::anthropic("", "model")
+                .base_url(url)
+                .retry_policy(RetryPolicy {
+                    initial_backoff: Duration::from_millis(1),
+                    restart_stream_before_content: true,
+                    ..RetryPolicy::default()
+                }),
+        )
+        .unwrap();
+        let stream = client
+            .chat_stream(&[Message::user_text("hi")], ChatOptions::default())
+            .await
+            .unwrap();
+        let events: Vec<_> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, StreamEvent::MessageStart { .. }))
+                .count(),
+            1
+        );
+        assert!(events.contains(&StreamEvent::TextDelta {
+            index: 0,
+            text: "hi".into()
+        }));
+        assert!(matches!(events.last(), Some(StreamEvent::Done { .. })));
     }
 }
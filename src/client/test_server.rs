@@ -0,0 +1,105 @@
+//! Local HTTP server for client tests.
+
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+pub(crate) struct RecordedRequest {
+    pub path: String,
+    pub body: Value,
+}
+
+/// How the server answers one connection.
+pub(crate) enum Reply {
+    /// Write a raw HTTP response and close the connection.
+    Respond(String),
+    /// Write a raw HTTP response, then keep the connection open without
+    /// sending anything more.
+    Stall(String),
+    /// Close the connection without answering.
+    Hangup,
+}
+
+impl From<String> for Reply {
+    fn from(response: String) -> Self {
+        Self::Respond(response)
+    }
+}
+
+pub(crate) fn json_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+pub(crate) fn sse_response(body: &str) -> String {
+    format!("HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n{body}")
+}
+
+/// Answer one connection per entry of `replies`, in order, and report each
+/// request's path and JSON body.
+pub(crate) async fn recording_server(
+    replies: Vec<impl Into<Reply>>,
+) -> (String, UnboundedReceiver<RecordedRequest>) {
+    let replies: Vec<Reply> = replies.into_iter().map(Into::into).collect();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = unbounded_channel();
+    tokio::spawn(async move {
+        for reply in replies {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let stall = matches!(reply, Reply::Stall(_));
+            let response = match reply {
+                Reply::Hangup => continue,
+                Reply::Respond(response) | Reply::Stall(response) => response,
+            };
+            if !record(&mut socket, &sender).await {
+                continue;
+            }
+            socket.write_all(response.as_bytes()).await.unwrap();
+            if stall {
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    drop(socket);
+                });
+            }
+        }
+    });
+    (url, receiver)
+}
+
+/// Read one request and send it to `sender`. Returns `false` if the client
+/// disconnected first.
+async fn record(socket: &mut TcpStream, sender: &UnboundedSender<RecordedRequest>) -> bool {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = socket.read(&mut buffer).await.unwrap_or(0);
+        if read == 0 {
+            return false;
+        }
+        request.extend_from_slice(&buffer[..read]);
+        let text = String::from_utf8_lossy(&request);
+        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if body.len() >= length {
+            let path = head.split(' ').nth(1).unwrap_or_default().to_string();
+            let body = serde_json::from_str(body).unwrap_or_default();
+            // Tests that only script responses drop the receiver.
+            let _ = sender.send(RecordedRequest { path, body });
+            return true;
+        }
+    }
+}
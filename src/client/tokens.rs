@@ -0,0 +1,211 @@
+use std::fmt;
+
+use tracing::debug;
+
+use super::LlmClient;
+use super::chat::ChatOptions;
+use super::error::LlmError;
+use crate::types::anthropic::{ContentBlock, Message, ToolResultContent};
+use crate::types::common::{Provider, ToolDefinition};
+
+/// Flat estimate for one image; providers bill roughly this much for a
+/// full-size image.
+const IMAGE_TOKENS: u64 = 1_600;
+
+/// Per-message framing overhead.
+const MESSAGE_TOKENS: u64 = 4;
+
+/// Local token counter used where the provider cannot count for us: token
+/// counting on OpenAI-compatible providers and context budgeting.
+///
+/// Implement it over a real tokenizer (e.g. tiktoken) for exact counts;
+/// [`HeuristicTokenizer`] is the default.
+pub trait Tokenizer: fmt::Debug + Send + Sync {
+    fn count_text(&self, text: &str) -> u64;
+
+    fn count_image(&self) -> u64 {
+        IMAGE_TOKENS
+    }
+}
+
+/// About four characters per token. Good enough for budgeting; not an exact
+/// count.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count_text(&self, text: &str) -> u64 {
+        (text.chars().count() as u64).div_ceil(4)
+    }
+}
+
+/// Prompt size of a request as counted by [`LlmClient::count_tokens`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenCount {
+    pub input_tokens: u64,
+    /// `true` when the provider counted, `false` for a local estimate.
+    pub exact: bool,
+}
+
+/// Estimate the prompt tokens of a request with `tokenizer`.
+pub fn estimate_tokens(
+    tokenizer: &dyn Tokenizer,
+    system: Option<&str>,
+    messages: &[Message],
+    tools: &[ToolDefinition],
+) -> u64 {
+    let tools: u64 = tools
+        .iter()
+        .map(|tool| json_tokens(tokenizer, &serde_json::to_value(tool).unwrap_or_default()))
+        .sum();
+    let messages: u64 = messages
+        .iter()
+        .map(|message| message_tokens(tokenizer, message))
+        .sum();
+    system.map_or(0, |system| tokenizer.count_text(system)) + messages + tools
+}
+
+pub(crate) fn message_tokens(tokenizer: &dyn Tokenizer, message: &Message) -> u64 {
+    MESSAGE_TOKENS
+        + message
+            .content
+            .iter()
+            .map(|block| block_tokens(tokenizer, block))
+            .sum::<u64>()
+}
+
+fn block_tokens(tokenizer: &dyn Tokenizer, block: &ContentBlock) -> u64 {
+    match block {
+        ContentBlock::Text { text, .. } => tokenizer.count_text(text),
+        ContentBlock::Thinking { thinking, .. } => tokenizer.count_text(thinking),
+        ContentBlock::Image { .. } => tokenizer.count_image(),
+        ContentBlock::ToolResult { content, .. } => match content {
+            ToolResultContent::Text(text) => tokenizer.count_text(text),
+            ToolResultContent::Blocks(blocks) => blocks
+                .iter()
+                .map(|block| block_tokens(tokenizer, block))
+                .sum(),
+        },
+        other => json_tokens(tokenizer, &serde_json::to_value(other).unwrap_or_default()),
+    }
+}
+
+fn json_tokens(tokenizer: &dyn Tokenizer, value: &serde_json::Value) -> u64 {
+    tokenizer.count_text(&value.to_string())
+}
+
+/// Fields the Anthropic count_tokens endpoint accepts from a Messages request.
+const COUNT_TOKENS_FIELDS: &[&str] = &[
+    "model",
+    "messages",
+    "system",
+    "tools",
+    "tool_choice",
+    "thinking",
+];
+
+impl LlmClient {
+    /// Count the prompt tokens `chat` would send for `messages`.
+    ///
+    /// Native Anthropic providers count with `/v1/messages/count_tokens`
+    /// using the same request construction as `chat`. OpenAI-compatible
+    /// providers have no such endpoint, so the configured
+    /// [`Tokenizer`] estimates locally.
+    pub async fn count_tokens(
+        &self,
+        messages: &[Message],
+        options: ChatOptions<'_>,
+    ) -> Result<TokenCount, LlmError> {
+        match self.config.provider {
+            Provider::Anthropic => {
+                let request = serde_json::to_value(self.anthropic_request(messages, &options)?)
+                    .map_err(|error| LlmError::Client(error.to_string()))?;
+                let body: serde_json::Map<_, _> = request
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(key, _)| COUNT_TOKENS_FIELDS.contains(&key.as_str()))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+
+                let url = self.endpoint("v1/messages/count_tokens");
+                debug!("POST {url}");
+                let response = self.send_json(&url, &body, options.timeout).await?;
+
+                #[derive(serde::Deserialize)]
+                struct Counted {
+                    input_tokens: u64,
+                }
+                let counted: Counted = serde_json::from_slice(&response)
+                    .map_err(|error| LlmError::ParseResponse(error.to_string()))?;
+                Ok(TokenCount {
+                    input_tokens: counted.input_tokens,
+                    exact: true,
+                })
+            }
+            Provider::OpenAiCompatible => Ok(TokenCount {
+                input_tokens: estimate_tokens(
+                    self.config.tokenizer.as_ref(),
+                    options.system,
+                    messages,
+                    options.tools.unwrap_or_default(),
+                ),
+                exact: false,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientConfig;
+    use crate::client::test_server::{json_response, recording_server};
+
+    #[derive(Debug)]
+    struct Words;
+
+    impl Tokenizer for Words {
+        fn count_text(&self, text: &str) -> u64 {
+            text.split_whitespace().count() as u64
+        }
+    }
+
+    #[tokio::test]
+    async fn counts_with_anthropic_endpoint_or_local_tokenizer() {
+        let (url, mut requests) =
+            recording_server(vec![json_response(r#"{"input_tokens":42}"#)]).await;
+        let messages = [Message::user_text("How many tokens is this?")];
+        let options = ChatOptions {
+            system: Some("Be brief."),
+            temperature: Some(0.5),
+            ..ChatOptions::default()
+        };
+
+        let anthropic =
+            LlmClient::new(ClientConfig::anthropic("", "claude").base_url(url)).unwrap();
+        let count = anthropic.count_tokens(&messages, options).await.unwrap();
+        assert_eq!(
+            count,
+            TokenCount {
+                input_tokens: 42,
+                exact: true
+            }
+        );
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.path, "/v1/messages/count_tokens");
+        assert_eq!(request.body["system"], "Be brief.");
+        assert!(request.body.get("max_tokens").is_none());
+        assert!(request.body.get("temperature").is_none());
+
+        let openai = LlmClient::new(ClientConfig::openai("", "gpt").tokenizer(Words)).unwrap();
+        let count = openai.count_tokens(&messages, options).await.unwrap();
+        assert_eq!(
+            count,
+            TokenCount {
+                input_tokens: 2 + MESSAGE_TOKENS + 5,
+                exact: false
+            }
+        );
+    }
+}
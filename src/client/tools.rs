@@ -0,0 +1,317 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+
+use futures_util::future::join_all;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use super::LlmClient;
+use super::chat::ChatOptions;
+use super::error::LlmError;
+use crate::types::anthropic::{ContentBlock, Message, MessagesResponse, ToolResultContent};
+use crate::types::common::{ToolDefinition, Usage};
+
+/// A client-executed function tool with a typed input.
+///
+/// The input schema sent to the model is derived from `Input` with
+/// `schemars`. Errors are reported back to the model as a `tool_result` with
+/// `is_error: true` rather than aborting the loop.
+pub trait Tool: Send + Sync + 'static {
+    type Input: DeserializeOwned + JsonSchema + Send;
+    type Output: Into<ToolResultContent>;
+    type Error: Display;
+
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn call(
+        &self,
+        input: Self::Input,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
+}
+
+type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<ToolResultContent, String>> + Send + 'a>>;
+
+/// Object-safe form of [`Tool`] taking raw JSON input.
+trait DynTool: Send + Sync {
+    fn call_json(&self, input: Value) -> ToolFuture<'_>;
+}
+
+impl<T: Tool> DynTool for T {
+    fn call_json(&self, input: Value) -> ToolFuture<'_> {
+        Box::pin(async move {
+            let input = serde_json::from_value(input)
+                .map_err(|error| format!("invalid input for {}: {error}", self.name()))?;
+            self.call(input)
+                .await
+                .map(Into::into)
+                .map_err(|error| error.to_string())
+        })
+    }
+}
+
+/// Set of [`Tool`]s offered to the model by [`LlmClient::run_tools`].
+#[derive(Default)]
+pub struct ToolRegistry {
+    definitions: Vec<ToolDefinition>,
+    tools: Vec<Box<dyn DynTool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tool, replacing any earlier tool with the same name. Fails with
+    /// [`LlmError::Config`] if the input schema cannot be serialized.
+    pub fn tool<T: Tool>(mut self, tool: T) -> Result<Self, LlmError> {
+        let schema = serde_json::to_value(schemars::schema_for!(T::Input))
+            .map_err(|error| LlmError::Config(format!("tool {}: {error}", tool.name())))?;
+        let definition = ToolDefinition::new(tool.name(), tool.description(), schema);
+        match self.position(tool.name()) {
+            Some(index) => {
+                warn!("Replacing tool {}", tool.name());
+                self.definitions[index] = definition;
+                self.tools[index] = Box::new(tool);
+            }
+            None => {
+                self.definitions.push(definition);
+                self.tools.push(Box::new(tool));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Definitions to send with a request, in registration order.
+    pub fn definitions(&self) -> &[ToolDefinition] {
+        &self.definitions
+    }
+
+    /// Run the named tool on raw JSON input. Unknown tools, invalid input and
+    /// tool failures are all reported as `Err` text for the model.
+    pub async fn call(&self, name: &str, input: Value) -> Result<ToolResultContent, String> {
+        match self.position(name) {
+            Some(index) => self.tools[index].call_json(input).await,
+            None => Err(format!("unknown tool {name}")),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.definitions
+            .iter()
+            .position(|definition| definition.name() == name)
+    }
+
+    /// Execute every tool call in `response` concurrently and collect the
+    /// results, in call order, into one user message.
+    async fn execute(&self, response: &MessagesResponse) -> Option<Message> {
+        let calls: Vec<_> = response
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse {
+                    id, name, input, ..
+                } => Some((id, name, input)),
+                _ => None,
+            })
+            .collect();
+        if calls.is_empty() {
+            return None;
+        }
+        let results = join_all(calls.into_iter().map(|(id, name, input)| async move {
+            debug!("Calling tool {name} ({id})");
+            match self.call(name, input.clone()).await {
+                Ok(content) => ContentBlock::tool_result(id, content, false),
+                Err(error) => {
+                    warn!("Tool {name} failed: {error}");
+                    ContentBlock::tool_result(id, error, true)
+                }
+            }
+        }))
+        .await;
+        Some(Message::tool_results(results))
+    }
+}
+
+/// Outcome of [`LlmClient::run_tools`].
+#[derive(Debug)]
+pub struct ToolRun {
+    /// The conversation including every assistant turn and tool result.
+    pub messages: Vec<Message>,
+    /// The last model response. Still requests tools if the iteration limit
+    /// was reached.
+    pub response: MessagesResponse,
+    /// Usage summed over all requests.
+    pub usage: Usage,
+    /// Number of tool-execution rounds performed.
+    pub iterations: usize,
+}
+
+impl LlmClient {
+    /// Chat with `tools` available, executing the model's tool calls and
+    /// sending back the results until it answers without calling a tool or
+    /// `max_iterations` rounds of tool calls have run.
+    ///
+    /// Calls made in the same response run concurrently. Tools in
+    /// `options.tools` (e.g. server tools) are offered alongside the
+    /// registry's, except those named like a registry tool; only registry
+    /// tools are executed here.
+    pub async fn run_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: &ToolRegistry,
+        max_iterations: usize,
+        options: ChatOptions<'_>,
+    ) -> Result<ToolRun, LlmError> {
+        // Registry tools replace same-named ones from `options.tools`.
+        let mut definitions: Vec<_> = options
+            .tools
+            .unwrap_or_default()
+            .iter()
+            .filter(|definition| tools.position(definition.name()).is_none())
+            .cloned()
+            .collect();
+        definitions.extend(tools.definitions().iter().cloned());
+        let options = ChatOptions {
+            tools: Some(&definitions),
+            ..options
+        };
+
+        let mut messages = messages;
+        let mut usage = Usage::default();
+        let mut iterations = 0;
+        loop {
+            let response = self.chat(&messages, options).await?;
+            if let Some(response_usage) = &response.usage {
+                usage += response_usage;
+            }
+            messages.push(Message::assistant(response.content.clone()));
+            if iterations < max_iterations
+                && response.has_tool_use()
+                && let Some(results) = tools.execute(&response).await
+            {
+                messages.push(results);
+                iterations += 1;
+                continue;
+            }
+            return Ok(ToolRun {
+                messages,
+                response,
+                usage,
+                iterations,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientConfig;
+    use crate::client::test_server::{json_response, recording_server};
+
+    #[derive(serde::Deserialize, schemars::JsonSchema)]
+    struct AddInput {
+        a: i64,
+        b: i64,
+    }
+
+    struct Add;
+
+    impl Tool for Add {
+        type Input = AddInput;
+        type Output = String;
+        type Error = String;
+
+        fn name(&self) -> &str {
+            "add"
+        }
+
+        fn description(&self) -> &str {
+            "Add two integers."
+        }
+
+        async fn call(&self, input: AddInput) -> Result<String, String> {
+            input
+                .a
+                .checked_add(input.b)
+                .map(|sum| sum.to_string())
+                .ok_or_else(|| "overflow".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_tool_calls_until_the_model_answers() {
+        let (url, mut requests) = recording_server(vec![
+            json_response(
+                r#"{"id":"chatcmpl-1","model":"gpt","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[
+                    {"id":"call_1","type":"function","function":{"name":"add","arguments":"{\"a\":2,\"b\":3}"}},
+                    {"id":"call_2","type":"function","function":{"name":"add","arguments":"{\"a\":9223372036854775807,\"b\":1}"}},
+                    {"id":"call_3","type":"function","function":{"name":"missing","arguments":"{}"}}
+                ]},"finish_reason":"tool_calls"}],"usage":{"prompt_tokens":10,"completion_tokens":5,"total_tokens":15}}"#,
+            ),
+            json_response(
+                r#"{"id":"chatcmpl-2","model":"gpt","choices":[{"index":0,"message":{"role":"assistant","content":"2 + 3 = 5"},"finish_reason":"stop"}],"usage":{"prompt_tokens":30,"completion_tokens":7,"total_tokens":37}}"#,
+            ),
+        ])
+        .await;
+        let client = LlmClient::new(ClientConfig::openai("", "gpt").base_url(url)).unwrap();
+        let registry = ToolRegistry::new().tool(Add).unwrap();
+        let extra_tools = [
+            ToolDefinition::new("add", "Stale definition.", serde_json::json!({})),
+            ToolDefinition::new("lookup", "Look something up.", serde_json::json!({})),
+        ];
+
+        let run = client
+            .run_tools(
+                vec![Message::user_text("What is 2 + 3?")],
+                &registry,
+                4,
+                ChatOptions {
+                    tools: Some(&extra_tools),
+                    ..ChatOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(run.response.text(), "2 + 3 = 5");
+        assert_eq!(run.iterations, 1);
+        assert_eq!(run.messages.len(), 4);
+        assert_eq!((run.usage.input_tokens, run.usage.output_tokens), (40, 12));
+
+        let first = requests.recv().await.unwrap().body;
+        let offered: Vec<_> = first["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["function"]["description"].clone())
+            .collect();
+        assert_eq!(offered, ["Look something up.", "Add two integers."]);
+        let second = requests.recv().await.unwrap().body;
+        let tool_messages: Vec<_> = second["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|message| message["role"] == "tool")
+            .map(|message| (message["tool_call_id"].clone(), message["content"].clone()))
+            .collect();
+        assert_eq!(tool_messages.len(), 3);
+        assert_eq!(tool_messages[0], ("call_1".into(), "5".into()));
+        assert!(
+            tool_messages[1].1.as_str().unwrap().contains("overflow"),
+            "{tool_messages:?}"
+        );
+        assert!(
+            tool_messages[2]
+                .1
+                .as_str()
+                .unwrap()
+                .contains("unknown tool missing")
+        );
+    }
+}
@@ -1,4 +1,7 @@
 pub mod cache_control;
+pub mod partial_json;
+pub mod schema;
+pub mod stream;
 pub mod thinking;
 pub mod to_anthropic;
 pub mod to_openai;
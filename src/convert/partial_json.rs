@@ -0,0 +1,115 @@
+use serde_json::Value;
+
+/// Best-effort parse of a JSON document that may be cut off mid-way, such as
+/// streamed tool call arguments.
+///
+/// Open strings, arrays and objects are closed; a trailing incomplete key,
+/// number or literal is dropped back to the last complete element. Returns
+/// `None` when nothing usable has arrived yet.
+pub fn parse_partial_json(input: &str) -> Option<Value> {
+    let input = input.trim_start();
+    if input.is_empty() {
+        return None;
+    }
+    if let Ok(value) = serde_json::from_str(input) {
+        return Some(value);
+    }
+
+    let mut stack = Vec::new();
+    // (byte offset to cut at, open containers at that point)
+    let mut cuts: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut escape_start = 0;
+    for (index, byte) in input.bytes().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+                escape_start = index;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                stack.push(byte);
+                cuts.push((index + 1, stack.clone()));
+            }
+            b'}' | b']' => {
+                stack.pop();
+            }
+            b',' => cuts.push((index, stack.clone())),
+            _ => {}
+        }
+    }
+
+    let mut head = input.to_string();
+    if in_string {
+        // Drop a dangling `\` or incomplete `\uXXXX` escape before closing.
+        let tail = &input[escape_start..];
+        if escaped || (tail.starts_with("\\u") && tail.len() < 6) {
+            head.truncate(escape_start);
+        }
+        head.push('"');
+    }
+    std::iter::once((head.as_str(), &stack))
+        .chain(
+            cuts.iter()
+                .rev()
+                .map(|(cut, stack)| (&input[..*cut], stack)),
+        )
+        .find_map(|(prefix, stack)| serde_json::from_str(&close(prefix, stack)).ok())
+}
+
+fn close(prefix: &str, stack: &[u8]) -> String {
+    let mut closed = prefix.trim_end().to_string();
+    for open in stack.iter().rev() {
+        closed.push(if *open == b'{' { '}' } else { ']' });
+    }
+    closed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn closes_truncated_json_at_the_last_complete_element() {
+        assert_eq!(parse_partial_json(""), None);
+        assert_eq!(parse_partial_json("{"), Some(json!({})));
+        assert_eq!(parse_partial_json(r#"{"que"#), Some(json!({})));
+        assert_eq!(
+            parse_partial_json(r#"{"query":"ru"#),
+            Some(json!({"query": "ru"}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"query":"rust","limit":1"#),
+            Some(json!({"query": "rust", "limit": 1}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"query":"rust","exact":tr"#),
+            Some(json!({"query": "rust"}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"tags":["a","b"#),
+            Some(json!({"tags": ["a", "b"]}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"text":"line\"#),
+            Some(json!({"text": "line"}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"text":"caf\u00"#),
+            Some(json!({"text": "caf"}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"a":{"b":1},"c":"#),
+            Some(json!({"a": {"b": 1}}))
+        );
+    }
+}
@@ -0,0 +1,524 @@
+use serde_json::{Map, Value};
+
+/// Target for [`normalize_schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaDialect {
+    /// OpenAI structured outputs with `strict: true`: every object closed
+    /// with `additionalProperties: false`, every property required (optional
+    /// ones made nullable), no `$ref`, and only the supported keywords.
+    OpenAiStrict,
+    /// Tool input schemas for either provider: `$ref`s inlined where
+    /// possible; everything else is kept. Schemas that cannot be inlined are
+    /// returned unchanged, since both providers resolve local `$ref`s.
+    ToolInput,
+}
+
+/// Result of [`normalize_schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedSchema {
+    pub schema: Value,
+    /// Constraints that were dropped or relaxed, each prefixed with the JSON
+    /// Pointer of the affected subschema. Empty when nothing was lost.
+    pub lossy: Vec<String>,
+}
+
+/// Keywords OpenAI strict mode rejects that only narrow the accepted values.
+const STRICT_UNSUPPORTED: &[&str] = &[
+    "minLength",
+    "maxLength",
+    "minProperties",
+    "maxProperties",
+    "uniqueItems",
+    "contains",
+    "minContains",
+    "maxContains",
+    "patternProperties",
+    "propertyNames",
+    "unevaluatedProperties",
+    "unevaluatedItems",
+    "dependentRequired",
+    "dependentSchemas",
+    "not",
+    "if",
+    "then",
+    "else",
+];
+
+/// Annotations OpenAI strict mode rejects; dropping them loses nothing.
+const STRICT_ANNOTATIONS: &[&str] = &[
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "$comment",
+    "$id",
+    "$anchor",
+];
+
+/// String formats OpenAI strict mode accepts.
+const STRICT_FORMATS: &[&str] = &[
+    "date-time",
+    "time",
+    "date",
+    "duration",
+    "email",
+    "hostname",
+    "ipv4",
+    "ipv6",
+    "uuid",
+];
+
+/// Keywords whose value is a single subschema.
+const SUBSCHEMA_KEYS: &[&str] = &[
+    "items",
+    "additionalProperties",
+    "not",
+    "if",
+    "then",
+    "else",
+    "contains",
+];
+
+/// Keywords whose value is a list of subschemas.
+const SUBSCHEMA_LIST_KEYS: &[&str] = &["anyOf", "oneOf", "allOf", "prefixItems"];
+
+/// Rewrite a JSON Schema (e.g. from `schemars::schema_for!`) into a form the
+/// provider accepts.
+///
+/// Local `$ref`s into `$defs`/`definitions` are inlined. Recursive types
+/// cannot be: strict mode rejects them, as it does a root that is not an
+/// object, while tool schemas keep their `$defs` and `$ref`s.
+pub fn normalize_schema(
+    schema: &Value,
+    dialect: SchemaDialect,
+) -> Result<NormalizedSchema, String> {
+    let mut definitions = Map::new();
+    for key in ["definitions", "$defs"] {
+        if let Some(Value::Object(defs)) = schema.get(key) {
+            definitions.extend(defs.clone());
+        }
+    }
+    let mut normalizer = Normalizer {
+        dialect,
+        definitions,
+        expanding: Vec::new(),
+        lossy: Vec::new(),
+    };
+    let visited = normalizer.visit(schema, "");
+    if dialect == SchemaDialect::ToolInput {
+        return Ok(NormalizedSchema {
+            schema: visited.unwrap_or_else(|_| schema.clone()),
+            lossy: normalizer.lossy,
+        });
+    }
+    let schema = visited?;
+    if schema.get("type").and_then(Value::as_str) != Some("object") {
+        return Err("root schema must be an object".into());
+    }
+    Ok(NormalizedSchema {
+        schema,
+        lossy: normalizer.lossy,
+    })
+}
+
+struct Normalizer {
+    dialect: SchemaDialect,
+    definitions: Map<String, Value>,
+    /// Definitions being inlined on the current path, to detect recursion.
+    expanding: Vec<String>,
+    lossy: Vec<String>,
+}
+
+impl Normalizer {
+    fn visit(&mut self, schema: &Value, path: &str) -> Result<Value, String> {
+        let mut map = match schema {
+            Value::Object(map) => map.clone(),
+            Value::Bool(true) if self.dialect == SchemaDialect::OpenAiStrict => {
+                return Err(format!(
+                    "#{path}: unconstrained values are not supported in strict mode"
+                ));
+            }
+            other => return Ok(other.clone()),
+        };
+
+        let depth = self.expanding.len();
+        while let Some(reference) = map.remove("$ref") {
+            let name = self.definition_name(&reference, path)?;
+            if self.expanding.contains(&name) {
+                return Err(format!(
+                    "#{path}: recursive reference to {name} cannot be inlined"
+                ));
+            }
+            let mut merged = self.definition(&name, path)?;
+            // Keywords next to `$ref` (e.g. `description`) take precedence.
+            merged.extend(map);
+            map = merged;
+            self.expanding.push(name);
+        }
+        for key in ["$defs", "definitions", "$schema"] {
+            map.remove(key);
+        }
+        if self.dialect == SchemaDialect::OpenAiStrict {
+            self.restrict(&mut map, path)?;
+        }
+
+        for key in SUBSCHEMA_KEYS {
+            if let Some(subschema) = map.get(*key).filter(|value| value.is_object()) {
+                let visited = self.visit(subschema, &format!("{path}/{key}"))?;
+                map.insert((*key).into(), visited);
+            }
+        }
+        for key in SUBSCHEMA_LIST_KEYS {
+            if let Some(Value::Array(subschemas)) = map.get(*key) {
+                let visited = subschemas
+                    .iter()
+                    .enumerate()
+                    .map(|(index, subschema)| {
+                        self.visit(subschema, &format!("{path}/{key}/{index}"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                map.insert((*key).into(), Value::Array(visited));
+            }
+        }
+        if let Some(Value::Object(properties)) = map.get("properties") {
+            let mut visited = Map::new();
+            for (name, property) in properties {
+                visited.insert(
+                    name.clone(),
+                    self.visit(property, &format!("{path}/properties/{name}"))?,
+                );
+            }
+            map.insert("properties".into(), Value::Object(visited));
+        }
+        if self.dialect == SchemaDialect::OpenAiStrict && is_object(&map) {
+            self.close_object(&mut map, path);
+        }
+
+        self.expanding.truncate(depth);
+        Ok(Value::Object(map))
+    }
+
+    fn definition_name(&self, reference: &Value, path: &str) -> Result<String, String> {
+        if reference == "#" {
+            return Err(format!(
+                "#{path}: recursive reference to the root schema cannot be inlined"
+            ));
+        }
+        reference
+            .as_str()
+            .and_then(|reference| {
+                reference
+                    .strip_prefix("#/$defs/")
+                    .or_else(|| reference.strip_prefix("#/definitions/"))
+            })
+            .map(|name| name.replace("~1", "/").replace("~0", "~"))
+            .ok_or_else(|| format!("#{path}: unsupported $ref {reference}"))
+    }
+
+    fn definition(&self, name: &str, path: &str) -> Result<Map<String, Value>, String> {
+        match self.definitions.get(name) {
+            Some(Value::Object(definition)) => Ok(definition.clone()),
+            Some(Value::Bool(true)) => Ok(Map::new()),
+            _ => Err(format!("#{path}: unknown definition {name}")),
+        }
+    }
+
+    /// Drop or rewrite the keywords strict mode rejects.
+    fn restrict(&mut self, map: &mut Map<String, Value>, path: &str) -> Result<(), String> {
+        if let Some(Value::Array(all)) = map.remove("allOf") {
+            for subschema in all {
+                let mut subschema = match subschema {
+                    Value::Object(subschema) => subschema,
+                    _ => continue,
+                };
+                while let Some(reference) = subschema.remove("$ref") {
+                    let name = self.definition_name(&reference, path)?;
+                    let mut merged = self.definition(&name, path)?;
+                    merged.extend(subschema);
+                    subschema = merged;
+                }
+                merge_all_of(map, subschema);
+            }
+        }
+        if let Some(one_of) = map.remove("oneOf") {
+            self.note(path, "oneOf relaxed to anyOf");
+            map.entry("anyOf").or_insert(one_of);
+        }
+        for key in STRICT_UNSUPPORTED {
+            if map.remove(*key).is_some() {
+                self.note(path, &format!("dropped unsupported {key}"));
+            }
+        }
+        for key in STRICT_ANNOTATIONS {
+            map.remove(*key);
+        }
+        if let Some(format) = map.get("format").and_then(Value::as_str) {
+            let numeric = matches!(
+                map.get("type").and_then(Value::as_str),
+                Some("integer" | "number")
+            );
+            if !STRICT_FORMATS.contains(&format) {
+                // Integer widths like `uint32` are still enforced on deserialize.
+                if !numeric {
+                    self.note(path, &format!("dropped unsupported format {format}"));
+                }
+                map.remove("format");
+            }
+        }
+        Ok(())
+    }
+
+    /// Require every property, making the optional ones nullable, and forbid
+    /// undeclared ones.
+    fn close_object(&mut self, map: &mut Map<String, Value>, path: &str) {
+        let required: Vec<String> = match map.get("required") {
+            Some(Value::Array(required)) => required
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut names = Vec::new();
+        if let Some(Value::Object(properties)) = map.get_mut("properties") {
+            for (name, property) in properties.iter_mut() {
+                if !required.contains(name) && make_nullable(property) {
+                    self.lossy.push(format!(
+                        "#{path}/properties/{name}: optional property made required and nullable"
+                    ));
+                }
+                names.push(Value::String(name.clone()));
+            }
+        }
+        map.insert("required".into(), Value::Array(names));
+        match map.insert("additionalProperties".into(), Value::Bool(false)) {
+            None | Some(Value::Bool(false)) => {}
+            Some(_) => self.note(path, "additional properties are no longer accepted"),
+        }
+    }
+
+    fn note(&mut self, path: &str, message: &str) {
+        self.lossy.push(format!("#{path}: {message}"));
+    }
+}
+
+fn is_object(map: &Map<String, Value>) -> bool {
+    map.contains_key("properties")
+        || match map.get("type") {
+            Some(Value::String(kind)) => kind == "object",
+            Some(Value::Array(kinds)) => kinds.iter().any(|kind| kind == "object"),
+            _ => false,
+        }
+}
+
+/// Fold an `allOf` member into the enclosing schema.
+fn merge_all_of(map: &mut Map<String, Value>, subschema: Map<String, Value>) {
+    for (key, value) in subschema {
+        match (key.as_str(), map.get_mut(&key), value) {
+            ("properties", Some(Value::Object(properties)), Value::Object(more)) => {
+                properties.extend(more);
+            }
+            ("required", Some(Value::Array(required)), Value::Array(more)) => {
+                for name in more {
+                    if !required.contains(&name) {
+                        required.push(name);
+                    }
+                }
+            }
+            (_, Some(_), _) => {}
+            (_, None, value) => {
+                map.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Allow `null` in addition to what `schema` accepts. Returns `false` if it
+/// already did.
+fn make_nullable(schema: &mut Value) -> bool {
+    let Value::Object(map) = schema else {
+        return false;
+    };
+    if let Some(Value::Array(any_of)) = map.get_mut("anyOf") {
+        if any_of.iter().any(accepts_null) {
+            return false;
+        }
+        any_of.push(serde_json::json!({ "type": "null" }));
+        return true;
+    }
+    let changed = match map.get_mut("type") {
+        Some(Value::String(kind)) if kind == "null" => false,
+        Some(Value::String(kind)) => {
+            let kind = std::mem::take(kind);
+            map.insert("type".into(), serde_json::json!([kind, "null"]));
+            true
+        }
+        Some(Value::Array(kinds)) if !kinds.iter().any(|kind| kind == "null") => {
+            kinds.push("null".into());
+            true
+        }
+        Some(_) => false,
+        None => {
+            let inner = std::mem::take(map);
+            map.insert(
+                "anyOf".into(),
+                serde_json::json!([inner, { "type": "null" }]),
+            );
+            return true;
+        }
+    };
+    if let Some(Value::Array(values)) = map.get_mut("enum")
+        && !values.contains(&Value::Null)
+    {
+        values.push(Value::Null);
+    }
+    changed
+}
+
+fn accepts_null(schema: &Value) -> bool {
+    match schema.get("type") {
+        Some(Value::String(kind)) => kind == "null",
+        Some(Value::Array(kinds)) => kinds.iter().any(|kind| kind == "null"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Order {
+        /// Customer placing the order.
+        customer: Customer,
+        items: Vec<Item>,
+        note: Option<String>,
+        #[serde(default)]
+        priority: u8,
+    }
+
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Customer {
+        #[schemars(length(max = 80))]
+        name: String,
+        #[schemars(email)]
+        email: String,
+    }
+
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Item {
+        sku: String,
+        quantity: u32,
+    }
+
+    #[allow(dead_code)]
+    #[derive(schemars::JsonSchema)]
+    struct Tree {
+        children: Vec<Tree>,
+    }
+
+    #[test]
+    fn normalizes_schemars_output_for_openai_strict_mode() {
+        let schema = serde_json::to_value(schemars::schema_for!(Order)).unwrap();
+        let normalized = normalize_schema(&schema, SchemaDialect::OpenAiStrict).unwrap();
+
+        assert_eq!(
+            normalized.schema,
+            json!({
+                "title": "Order",
+                "type": "object",
+                "properties": {
+                    "customer": {
+                        "description": "Customer placing the order.",
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "email": { "type": "string", "format": "email" }
+                        },
+                        "required": ["email", "name"],
+                        "additionalProperties": false
+                    },
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "sku": { "type": "string" },
+                                "quantity": { "type": "integer", "minimum": 0 }
+                            },
+                            "required": ["quantity", "sku"],
+                            "additionalProperties": false
+                        }
+                    },
+                    "note": { "type": ["string", "null"] },
+                    "priority": { "type": ["integer", "null"], "minimum": 0, "maximum": 255 }
+                },
+                "required": ["customer", "items", "note", "priority"],
+                "additionalProperties": false
+            })
+        );
+        assert_eq!(
+            normalized.lossy,
+            [
+                "#/properties/customer/properties/name: dropped unsupported maxLength",
+                "#/properties/priority: optional property made required and nullable",
+            ]
+        );
+    }
+
+    #[test]
+    fn inlines_tool_schemas_and_keeps_recursive_ones() {
+        let schema = serde_json::to_value(schemars::schema_for!(Order)).unwrap();
+        let normalized = normalize_schema(&schema, SchemaDialect::ToolInput).unwrap();
+        assert!(normalized.lossy.is_empty());
+        assert!(normalized.schema.get("$defs").is_none());
+        assert_eq!(
+            normalized.schema["properties"]["customer"]["properties"]["name"]["maxLength"],
+            80
+        );
+        assert_eq!(normalized.schema["required"], json!(["customer", "items"]));
+
+        let tree = serde_json::to_value(schemars::schema_for!(Tree)).unwrap();
+        assert_eq!(
+            normalize_schema(&tree, SchemaDialect::ToolInput)
+                .unwrap()
+                .schema,
+            tree
+        );
+        assert_eq!(
+            normalize_schema(&tree, SchemaDialect::OpenAiStrict).unwrap_err(),
+            "#/properties/children/items: recursive reference to the root schema cannot be inlined"
+        );
+
+        let named = json!({
+            "type": "object",
+            "properties": { "root": { "$ref": "#/$defs/Node" } },
+            "$defs": {
+                "Node": {
+                    "type": "object",
+                    "properties": { "next": { "$ref": "#/$defs/Node" } }
+                }
+            }
+        });
+        assert_eq!(
+            normalize_schema(&named, SchemaDialect::ToolInput)
+                .unwrap()
+                .schema,
+            named
+        );
+
+        let string = json!({ "type": "string" });
+        assert_eq!(
+            normalize_schema(&string, SchemaDialect::ToolInput)
+                .unwrap()
+                .schema,
+            string
+        );
+        assert!(normalize_schema(&string, SchemaDialect::OpenAiStrict).is_err());
+    }
+}
@@ -0,0 +1,1028 @@
+use serde_json::{Value, json};
+
+use super::to_openai::usage_to_openai;
+use crate::types::common::{Provider, StopReason, Usage};
+use crate::types::openai::ReasoningDetail;
+use crate::types::stream::{BlockKind, StreamEvent};
+
+/// Decodes provider SSE events into [`StreamEvent`]s.
+///
+/// Anthropic events map one-to-one. OpenAI chunks carry no block lifecycle,
+/// so the decoder synthesizes `MessageStart`, `BlockStart` and `BlockStop`
+/// around consecutive reasoning, text and tool call deltas.
+#[derive(Debug)]
+pub struct StreamDecoder {
+    provider: Provider,
+    started: bool,
+    /// OpenAI: index and kind of the block currently receiving deltas.
+    open: Option<(usize, OpenBlock)>,
+    next_index: usize,
+    /// OpenAI: (tool call index, block index).
+    tool_blocks: Vec<(u64, usize)>,
+    stop_reason: Option<StopReason>,
+    done: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpenBlock {
+    Text,
+    Thinking,
+    ToolUse,
+    RedactedThinking,
+}
+
+impl StreamDecoder {
+    pub fn new(provider: Provider) -> Self {
+        Self {
+            provider,
+            started: false,
+            open: None,
+            next_index: 0,
+            tool_blocks: Vec::new(),
+            stop_reason: None,
+            done: false,
+        }
+    }
+
+    /// Decode one SSE event. `event` is the SSE event name (empty or
+    /// `message` when absent), `data` its payload.
+    pub fn decode(&mut self, event: &str, data: &str) -> Result<Vec<StreamEvent>, String> {
+        match self.provider {
+            Provider::OpenAiCompatible => self.decode_openai(data),
+            Provider::Anthropic => self.decode_anthropic(event, data),
+        }
+    }
+
+    /// Events to emit when the byte stream ends. Emits `Done` if the provider
+    /// reported a stop reason but the terminal event never arrived.
+    pub fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        if self.done || self.stop_reason.is_none() {
+            return events;
+        }
+        self.close_block(&mut events);
+        self.done = true;
+        events.push(StreamEvent::Done {
+            stop_reason: self.stop_reason.take(),
+        });
+        events
+    }
+
+    fn decode_anthropic(&mut self, event: &str, data: &str) -> Result<Vec<StreamEvent>, String> {
+        let value: Value = serde_json::from_str(data).map_err(|error| error.to_string())?;
+        let event = match event {
+            "" | "message" => value
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+            event => event,
+        };
+        let index = value
+            .get("index")
+            .and_then(Value::as_u64)
+            .unwrap_or_default() as usize;
+        let text = |pointer: &str| {
+            value
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let mut events = Vec::new();
+        match event {
+            "message_start" => {
+                events.push(StreamEvent::MessageStart {
+                    id: value
+                        .pointer("/message/id")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    model: value
+                        .pointer("/message/model")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                });
+                if let Some(usage) = value.pointer("/message/usage") {
+                    events.push(StreamEvent::Usage {
+                        usage: anthropic_usage(usage),
+                    });
+                }
+            }
+            "content_block_start" => {
+                let block = value.get("content_block").cloned().unwrap_or_default();
+                let kind = match block.get("type").and_then(Value::as_str) {
+                    Some("text") => BlockKind::Text,
+                    Some("thinking") => BlockKind::Thinking,
+                    Some("redacted_thinking") => BlockKind::RedactedThinking {
+                        data: text("/content_block/data"),
+                    },
+                    Some("tool_use") => BlockKind::ToolUse {
+                        id: text("/content_block/id"),
+                        name: text("/content_block/name"),
+                    },
+                    Some("server_tool_use") => BlockKind::ServerToolUse {
+                        id: text("/content_block/id"),
+                        name: text("/content_block/name"),
+                    },
+                    _ => BlockKind::Other { block },
+                };
+                events.push(StreamEvent::BlockStart { index, kind });
+                let initial_text = text("/content_block/text");
+                if !initial_text.is_empty() {
+                    events.push(StreamEvent::TextDelta {
+                        index,
+                        text: initial_text,
+                    });
+                }
+                let initial_thinking = text("/content_block/thinking");
+                if !initial_thinking.is_empty() {
+                    events.push(StreamEvent::ThinkingDelta {
+                        index,
+                        text: initial_thinking,
+                    });
+                }
+            }
+            "content_block_delta" => {
+                let delta = match value.pointer("/delta/type").and_then(Value::as_str) {
+                    Some("text_delta") => Some(StreamEvent::TextDelta {
+                        index,
+                        text: text("/delta/text"),
+                    }),
+                    Some("thinking_delta") => Some(StreamEvent::ThinkingDelta {
+                        index,
+                        text: text("/delta/thinking"),
+                    }),
+                    Some("signature_delta") => Some(StreamEvent::ThinkingSignature {
+                        index,
+                        signature: text("/delta/signature"),
+                    }),
+                    Some("input_json_delta") => Some(StreamEvent::ToolCallDelta {
+                        index,
+                        arguments: text("/delta/partial_json"),
+                    }),
+                    _ => None,
+                };
+                events.extend(delta);
+            }
+            "content_block_stop" => events.push(StreamEvent::BlockStop { index }),
+            "message_delta" => {
+                if let Some(reason) = value.pointer("/delta/stop_reason").and_then(Value::as_str) {
+                    self.stop_reason = Some(match StopReason::from_anthropic(reason) {
+                        StopReason::StopSequence(None) => StopReason::StopSequence(
+                            value
+                                .pointer("/delta/stop_sequence")
+                                .and_then(Value::as_str)
+                                .map(str::to_string),
+                        ),
+                        other => other,
+                    });
+                }
+                if let Some(usage) = value.get("usage") {
+                    events.push(StreamEvent::Usage {
+                        usage: anthropic_usage(usage),
+                    });
+                }
+            }
+            "message_stop" => {
+                self.done = true;
+                events.push(StreamEvent::Done {
+                    stop_reason: self.stop_reason.take(),
+                });
+            }
+            "ping" => events.push(StreamEvent::Ping),
+            "error" => events.push(StreamEvent::Error {
+                error_type: text("/error/type"),
+                message: text("/error/message"),
+            }),
+            _ => {}
+        }
+        Ok(events)
+    }
+
+    fn decode_openai(&mut self, data: &str) -> Result<Vec<StreamEvent>, String> {
+        let mut events = Vec::new();
+        if data.trim() == "[DONE]" {
+            self.close_block(&mut events);
+            self.done = true;
+            events.push(StreamEvent::Done {
+                stop_reason: self.stop_reason.take(),
+            });
+            return Ok(events);
+        }
+        let value: Value = serde_json::from_str(data).map_err(|error| error.to_string())?;
+        if let Some(error) = value.get("error") {
+            let field = |name: &str| {
+                error
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            events.push(StreamEvent::Error {
+                error_type: field("type"),
+                message: field("message"),
+            });
+            return Ok(events);
+        }
+        if !self.started {
+            self.started = true;
+            events.push(StreamEvent::MessageStart {
+                id: value.get("id").and_then(Value::as_str).map(str::to_string),
+                model: value
+                    .get("model")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            });
+        }
+        for choice in value
+            .get("choices")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let delta = choice.get("delta").unwrap_or(&Value::Null);
+            let reasoning = delta
+                .get("reasoning_content")
+                .or_else(|| delta.get("reasoning"))
+                .and_then(Value::as_str);
+            if let Some(text) = reasoning.filter(|text| !text.is_empty()) {
+                let index = self.open_block(OpenBlock::Thinking, &mut events);
+                events.push(StreamEvent::ThinkingDelta {
+                    index,
+                    text: text.into(),
+                });
+            }
+            // OpenRouter repeats the reasoning text in `reasoning_details`;
+            // only signatures, encrypted blocks and detail-only text are new.
+            for detail in delta
+                .get("reasoning_details")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                match serde_json::from_value(detail.clone()) {
+                    Ok(ReasoningDetail::Text { text, signature }) => {
+                        let index = self.open_block(OpenBlock::Thinking, &mut events);
+                        if reasoning.is_none() && !text.is_empty() {
+                            events.push(StreamEvent::ThinkingDelta { index, text });
+                        }
+                        if let Some(signature) = signature {
+                            events.push(StreamEvent::ThinkingSignature { index, signature });
+                        }
+                    }
+                    Ok(ReasoningDetail::Encrypted { data }) => {
+                        self.start_block(
+                            OpenBlock::RedactedThinking,
+                            BlockKind::RedactedThinking { data },
+                            &mut events,
+                        );
+                        self.close_block(&mut events);
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(text) = delta
+                .get("content")
+                .and_then(Value::as_str)
+                .filter(|text| !text.is_empty())
+            {
+                let index = self.open_block(OpenBlock::Text, &mut events);
+                events.push(StreamEvent::TextDelta {
+                    index,
+                    text: text.into(),
+                });
+            }
+            for call in delta
+                .get("tool_calls")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let call_index = call
+                    .get("index")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default();
+                let index = match self
+                    .tool_blocks
+                    .iter()
+                    .find(|(tool_index, _)| *tool_index == call_index)
+                {
+                    Some((_, index)) => *index,
+                    None => {
+                        let field = |pointer: &str| {
+                            call.pointer(pointer)
+                                .and_then(Value::as_str)
+                                .unwrap_or_default()
+                                .to_string()
+                        };
+                        let kind = BlockKind::ToolUse {
+                            id: field("/id"),
+                            name: field("/function/name"),
+                        };
+                        let index = self.start_block(OpenBlock::ToolUse, kind, &mut events);
+                        self.tool_blocks.push((call_index, index));
+                        index
+                    }
+                };
+                if let Some(arguments) = call
+                    .pointer("/function/arguments")
+                    .and_then(Value::as_str)
+                    .filter(|arguments| !arguments.is_empty())
+                {
+                    events.push(StreamEvent::ToolCallDelta {
+                        index,
+                        arguments: arguments.into(),
+                    });
+                }
+            }
+            if let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) {
+                self.close_block(&mut events);
+                self.stop_reason = Some(StopReason::from_openai(reason));
+            }
+        }
+        if let Some(usage) = value.get("usage").filter(|usage| !usage.is_null()) {
+            events.push(StreamEvent::Usage {
+                usage: openai_usage(usage),
+            });
+        }
+        Ok(events)
+    }
+
+    /// Index of the open block of `kind`, starting a new one if needed.
+    fn open_block(&mut self, kind: OpenBlock, events: &mut Vec<StreamEvent>) -> usize {
+        match self.open {
+            Some((index, open)) if open == kind => index,
+            _ => {
+                let block_kind = match kind {
+                    OpenBlock::Thinking => BlockKind::Thinking,
+                    _ => BlockKind::Text,
+                };
+                self.start_block(kind, block_kind, events)
+            }
+        }
+    }
+
+    fn start_block(
+        &mut self,
+        open: OpenBlock,
+        kind: BlockKind,
+        events: &mut Vec<StreamEvent>,
+    ) -> usize {
+        self.close_block(events);
+        let index = self.next_index;
+        self.next_index += 1;
+        self.open = Some((index, open));
+        events.push(StreamEvent::BlockStart { index, kind });
+        index
+    }
+
+    fn close_block(&mut self, events: &mut Vec<StreamEvent>) {
+        if let Some((index, _)) = self.open.take() {
+            events.push(StreamEvent::BlockStop { index });
+        }
+    }
+}
+
+fn openai_usage(usage: &Value) -> Usage {
+    Usage {
+        input_tokens: usage
+            .get("prompt_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+        output_tokens: usage
+            .get("completion_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+        cache_creation_input_tokens: usage
+            .pointer("/prompt_tokens_details/cache_write_tokens")
+            .and_then(Value::as_u64),
+        cache_read_input_tokens: usage
+            .pointer("/prompt_tokens_details/cached_tokens")
+            .and_then(Value::as_u64),
+        reasoning_tokens: usage
+            .pointer("/completion_tokens_details/reasoning_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+        cost: usage.get("cost").and_then(Value::as_f64),
+    }
+}
+
+fn anthropic_usage(value: &Value) -> Usage {
+    Usage {
+        input_tokens: value
+            .get("input_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+        output_tokens: value
+            .get("output_tokens")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+        cache_creation_input_tokens: value
+            .get("cache_creation_input_tokens")
+            .and_then(Value::as_u64),
+        cache_read_input_tokens: value.get("cache_read_input_tokens").and_then(Value::as_u64),
+        reasoning_tokens: 0,
+        cost: None,
+    }
+}
+
+/// Encodes [`StreamEvent`]s as SSE frames in either wire format, for
+/// relaying a stream from one provider format to the other.
+///
+/// Anthropic output follows the Messages streaming protocol; `Usage` is
+/// folded into `message_start` and the final `message_delta`. OpenAI output
+/// is `chat.completion.chunk` frames, a trailing usage chunk and `[DONE]`.
+/// Server tool blocks have no OpenAI equivalent and are skipped there.
+#[derive(Debug)]
+pub struct StreamEncoder {
+    target: Provider,
+    id: String,
+    model: String,
+    created: u64,
+    /// `MessageStart` waiting for the usage event that follows it.
+    pending_start: bool,
+    started: bool,
+    usage: Usage,
+    /// OpenAI: (block index, tool call index).
+    tool_calls: Vec<(usize, usize)>,
+}
+
+impl StreamEncoder {
+    pub fn new(target: Provider) -> Self {
+        Self {
+            target,
+            id: String::new(),
+            model: String::new(),
+            created: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            pending_start: false,
+            started: false,
+            usage: Usage::default(),
+            tool_calls: Vec::new(),
+        }
+    }
+
+    /// SSE frames for one event; empty when the event has no representation
+    /// in the target format.
+    pub fn encode(&mut self, event: &StreamEvent) -> String {
+        match self.target {
+            Provider::Anthropic => self.encode_anthropic(event),
+            Provider::OpenAiCompatible => self.encode_openai(event),
+        }
+    }
+
+    fn encode_anthropic(&mut self, event: &StreamEvent) -> String {
+        let mut out = String::new();
+        match event {
+            StreamEvent::MessageStart { id, model } => {
+                self.set_message(id, model);
+                self.pending_start = true;
+                return out;
+            }
+            StreamEvent::Usage { usage } if self.pending_start || !self.started => {
+                self.usage.merge(usage);
+                self.pending_start = true;
+                return out;
+            }
+            _ => {}
+        }
+        if self.pending_start || !self.started {
+            self.pending_start = false;
+            self.started = true;
+            anthropic_frame(
+                &mut out,
+                "message_start",
+                json!({
+                    "type": "message_start",
+                    "message": {
+                        "id": self.id,
+                        "type": "message",
+                        "role": "assistant",
+                        "model": self.model,
+                        "content": [],
+                        "stop_reason": null,
+                        "stop_sequence": null,
+                        "usage": self.usage,
+                    }
+                }),
+            );
+        }
+        match event {
+            StreamEvent::MessageStart { .. } => {}
+            StreamEvent::BlockStart { index, kind } => {
+                let block = match kind {
+                    BlockKind::Text => json!({ "type": "text", "text": "" }),
+                    BlockKind::Thinking => json!({ "type": "thinking", "thinking": "" }),
+                    BlockKind::RedactedThinking { data } => {
+                        json!({ "type": "redacted_thinking", "data": data })
+                    }
+                    BlockKind::ToolUse { id, name } => {
+                        json!({ "type": "tool_use", "id": id, "name": name, "input": {} })
+                    }
+                    BlockKind::ServerToolUse { id, name } => {
+                        json!({ "type": "server_tool_use", "id": id, "name": name, "input": {} })
+                    }
+                    BlockKind::Other { block } => block.clone(),
+                };
+                anthropic_frame(
+                    &mut out,
+                    "content_block_start",
+                    json!({ "type": "content_block_start", "index": index, "content_block": block }),
+                );
+            }
+            StreamEvent::TextDelta { index, text } => anthropic_delta(
+                &mut out,
+                *index,
+                json!({ "type": "text_delta", "text": text }),
+            ),
+            StreamEvent::ThinkingDelta { index, text } => anthropic_delta(
+                &mut out,
+                *index,
+                json!({ "type": "thinking_delta", "thinking": text }),
+            ),
+            StreamEvent::ThinkingSignature { index, signature } => anthropic_delta(
+                &mut out,
+                *index,
+                json!({ "type": "signature_delta", "signature": signature }),
+            ),
+            StreamEvent::ToolCallDelta { index, arguments } => anthropic_delta(
+                &mut out,
+                *index,
+                json!({ "type": "input_json_delta", "partial_json": arguments }),
+            ),
+            StreamEvent::BlockStop { index } => anthropic_frame(
+                &mut out,
+                "content_block_stop",
+                json!({ "type": "content_block_stop", "index": index }),
+            ),
+            StreamEvent::Usage { usage } => self.usage.merge(usage),
+            StreamEvent::ToolCallPartial { .. } | StreamEvent::ToolCallComplete { .. } => {}
+            StreamEvent::Ping => anthropic_frame(&mut out, "ping", json!({ "type": "ping" })),
+            StreamEvent::Error {
+                error_type,
+                message,
+            } => anthropic_frame(
+                &mut out,
+                "error",
+                json!({
+                    "type": "error",
+                    "error": { "type": error_type, "message": message }
+                }),
+            ),
+            StreamEvent::Done { stop_reason } => {
+                let stop_reason = stop_reason.clone().unwrap_or(StopReason::EndTurn);
+                let stop_sequence = match &stop_reason {
+                    StopReason::StopSequence(sequence) => sequence.clone(),
+                    _ => None,
+                };
+                anthropic_frame(
+                    &mut out,
+                    "message_delta",
+                    json!({
+                        "type": "message_delta",
+                        "delta": {
+                            "stop_reason": stop_reason.to_anthropic(),
+                            "stop_sequence": stop_sequence,
+                        },
+                        "usage": self.usage,
+                    }),
+                );
+                anthropic_frame(&mut out, "message_stop", json!({ "type": "message_stop" }));
+            }
+        }
+        out
+    }
+
+    fn encode_openai(&mut self, event: &StreamEvent) -> String {
+        let mut out = String::new();
+        match event {
+            StreamEvent::MessageStart { id, model } => {
+                self.set_message(id, model);
+                self.started = true;
+                self.openai_chunk(
+                    &mut out,
+                    json!({ "role": "assistant", "content": "" }),
+                    None,
+                );
+            }
+            StreamEvent::BlockStart { index, kind } => match kind {
+                BlockKind::ToolUse { id, name } => {
+                    let call_index = self.tool_calls.len();
+                    self.tool_calls.push((*index, call_index));
+                    self.openai_chunk(
+                        &mut out,
+                        json!({ "tool_calls": [{
+                            "index": call_index,
+                            "id": id,
+                            "type": "function",
+                            "function": { "name": name, "arguments": "" }
+                        }] }),
+                        None,
+                    );
+                }
+                BlockKind::RedactedThinking { data } => self.openai_chunk(
+                    &mut out,
+                    json!({ "reasoning_details": [{ "type": "reasoning.encrypted", "data": data }] }),
+                    None,
+                ),
+                _ => {}
+            },
+            StreamEvent::TextDelta { text, .. } => {
+                self.openai_chunk(&mut out, json!({ "content": text }), None);
+            }
+            StreamEvent::ThinkingDelta { text, .. } => {
+                self.openai_chunk(&mut out, json!({ "reasoning_content": text }), None);
+            }
+            StreamEvent::ThinkingSignature { signature, .. } => self.openai_chunk(
+                &mut out,
+                json!({ "reasoning_details": [{
+                    "type": "reasoning.text",
+                    "text": "",
+                    "signature": signature
+                }] }),
+                None,
+            ),
+            StreamEvent::ToolCallDelta { index, arguments } => {
+                if let Some(&(_, call_index)) = self
+                    .tool_calls
+                    .iter()
+                    .find(|(block_index, _)| block_index == index)
+                {
+                    self.openai_chunk(
+                        &mut out,
+                        json!({ "tool_calls": [{
+                            "index": call_index,
+                            "function": { "arguments": arguments }
+                        }] }),
+                        None,
+                    );
+                }
+            }
+            StreamEvent::BlockStop { .. }
+            | StreamEvent::ToolCallPartial { .. }
+            | StreamEvent::ToolCallComplete { .. } => {}
+            StreamEvent::Usage { usage } => self.usage.merge(usage),
+            StreamEvent::Ping => out.push_str(": ping\n\n"),
+            StreamEvent::Error {
+                error_type,
+                message,
+            } => openai_frame(
+                &mut out,
+                &json!({ "error": { "type": error_type, "message": message } }),
+            ),
+            StreamEvent::Done { stop_reason } => {
+                let finish_reason = stop_reason
+                    .as_ref()
+                    .unwrap_or(&StopReason::EndTurn)
+                    .to_openai()
+                    .to_string();
+                self.openai_chunk(&mut out, json!({}), Some(finish_reason));
+                if self.usage != Usage::default() {
+                    let chunk = json!({
+                        "id": self.id,
+                        "object": "chat.completion.chunk",
+                        "created": self.created,
+                        "model": self.model,
+                        "choices": [],
+                        "usage": usage_to_openai(&self.usage),
+                    });
+                    openai_frame(&mut out, &chunk);
+                }
+                out.push_str("data: [DONE]\n\n");
+            }
+        }
+        out
+    }
+
+    fn set_message(&mut self, id: &Option<String>, model: &Option<String>) {
+        if let Some(id) = id {
+            self.id.clone_from(id);
+        }
+        if let Some(model) = model {
+            self.model.clone_from(model);
+        }
+    }
+
+    fn openai_chunk(&self, out: &mut String, delta: Value, finish_reason: Option<String>) {
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        });
+        openai_frame(out, &chunk);
+    }
+}
+
+fn anthropic_frame(out: &mut String, event: &str, data: Value) {
+    out.push_str(&format!("event: {event}\ndata: {data}\n\n"));
+}
+
+fn anthropic_delta(out: &mut String, index: usize, delta: Value) {
+    anthropic_frame(
+        out,
+        "content_block_delta",
+        json!({ "type": "content_block_delta", "index": index, "delta": delta }),
+    );
+}
+
+fn openai_frame(out: &mut String, data: &Value) {
+    out.push_str(&format!("data: {data}\n\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(provider: Provider, events: &[(&str, &str)]) -> Vec<StreamEvent> {
+        let mut decoder = StreamDecoder::new(provider);
+        let mut decoded = Vec::new();
+        for (event, data) in events {
+            decoded.extend(decoder.decode(event, data).unwrap());
+        }
+        decoded.extend(decoder.finish());
+        decoded
+    }
+
+    #[test]
+    fn synthesizes_block_lifecycle_for_openai_chunks() {
+        let events = decode_all(
+            Provider::OpenAiCompatible,
+            &[
+                (
+                    "",
+                    r#"{"id":"chatcmpl-1","model":"gpt","choices":[{"delta":{"reasoning":"hmm"}}]}"#,
+                ),
+                ("", r#"{"choices":[{"delta":{"content":"Hi"}}]}"#),
+                (
+                    "",
+                    r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"search","arguments":"{\"q\""}}]}}]}"#,
+                ),
+                (
+                    "",
+                    r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":":1}"}}]},"finish_reason":"tool_calls"}]}"#,
+                ),
+                (
+                    "",
+                    r#"{"choices":[],"usage":{"prompt_tokens":3,"completion_tokens":4}}"#,
+                ),
+                ("", "[DONE]"),
+            ],
+        );
+        assert_eq!(
+            events[..4],
+            [
+                StreamEvent::MessageStart {
+                    id: Some("chatcmpl-1".into()),
+                    model: Some("gpt".into())
+                },
+                StreamEvent::BlockStart {
+                    index: 0,
+                    kind: BlockKind::Thinking
+                },
+                StreamEvent::ThinkingDelta {
+                    index: 0,
+                    text: "hmm".into()
+                },
+                StreamEvent::BlockStop { index: 0 },
+            ]
+        );
+        assert!(events.contains(&StreamEvent::BlockStart {
+            index: 2,
+            kind: BlockKind::ToolUse {
+                id: "call_1".into(),
+                name: "search".into()
+            }
+        }));
+        assert!(events.contains(&StreamEvent::ToolCallDelta {
+            index: 2,
+            arguments: ":1}".into()
+        }));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, StreamEvent::Done { .. }))
+                .count(),
+            1
+        );
+        assert_eq!(
+            events.last(),
+            Some(&StreamEvent::Done {
+                stop_reason: Some(StopReason::ToolUse)
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_anthropic_lifecycle_with_single_done() {
+        let events = decode_all(
+            Provider::Anthropic,
+            &[
+                (
+                    "message_start",
+                    r#"{"message":{"id":"msg_1","model":"claude","usage":{"input_tokens":5}}}"#,
+                ),
+                ("ping", r#"{"type":"ping"}"#),
+                (
+                    "content_block_start",
+                    r#"{"index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+                ),
+                (
+                    "content_block_delta",
+                    r#"{"index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#,
+                ),
+                ("content_block_stop", r#"{"index":0}"#),
+                (
+                    "message_delta",
+                    r#"{"delta":{"stop_reason":"stop_sequence","stop_sequence":"END"},"usage":{"output_tokens":9}}"#,
+                ),
+                ("message_stop", r#"{"type":"message_stop"}"#),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                StreamEvent::MessageStart {
+                    id: Some("msg_1".into()),
+                    model: Some("claude".into())
+                },
+                StreamEvent::Usage {
+                    usage: Usage {
+                        input_tokens: 5,
+                        ..Usage::default()
+                    }
+                },
+                StreamEvent::Ping,
+                StreamEvent::BlockStart {
+                    index: 0,
+                    kind: BlockKind::Thinking
+                },
+                StreamEvent::ThinkingSignature {
+                    index: 0,
+                    signature: "sig".into()
+                },
+                StreamEvent::BlockStop { index: 0 },
+                StreamEvent::Usage {
+                    usage: Usage {
+                        output_tokens: 9,
+                        ..Usage::default()
+                    }
+                },
+                StreamEvent::Done {
+                    stop_reason: Some(StopReason::StopSequence(Some("END".into())))
+                },
+            ]
+        );
+    }
+
+    /// Split encoded SSE text back into (event, data) pairs.
+    fn frames(sse: &str) -> Vec<(String, String)> {
+        sse.split("\n\n")
+            .filter(|frame| !frame.is_empty() && !frame.starts_with(':'))
+            .map(|frame| {
+                let mut event = String::new();
+                let mut data = String::new();
+                for line in frame.lines() {
+                    if let Some(value) = line.strip_prefix("event: ") {
+                        event = value.into();
+                    } else if let Some(value) = line.strip_prefix("data: ") {
+                        data = value.into();
+                    }
+                }
+                (event, data)
+            })
+            .collect()
+    }
+
+    fn transcode(from: Provider, to: Provider, input: &[(&str, &str)]) -> Vec<StreamEvent> {
+        let mut encoder = StreamEncoder::new(to);
+        let sse: String = decode_all(from, input)
+            .iter()
+            .map(|event| encoder.encode(event))
+            .collect();
+        let frames = frames(&sse);
+        let frames: Vec<(&str, &str)> = frames
+            .iter()
+            .map(|(event, data)| (event.as_str(), data.as_str()))
+            .collect();
+        decode_all(to, &frames)
+    }
+
+    #[test]
+    fn transcodes_openai_tool_call_stream_to_anthropic_sse() {
+        let events = transcode(
+            Provider::OpenAiCompatible,
+            Provider::Anthropic,
+            &[
+                (
+                    "",
+                    r#"{"id":"chatcmpl-1","model":"gpt","choices":[{"delta":{"content":"Hi"}}]}"#,
+                ),
+                (
+                    "",
+                    r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"search","arguments":"{}"}}]},"finish_reason":"tool_calls"}]}"#,
+                ),
+                (
+                    "",
+                    r#"{"choices":[],"usage":{"prompt_tokens":3,"completion_tokens":4}}"#,
+                ),
+                ("", "[DONE]"),
+            ],
+        );
+        assert_eq!(
+            events[0],
+            StreamEvent::MessageStart {
+                id: Some("chatcmpl-1".into()),
+                model: Some("gpt".into())
+            }
+        );
+        assert!(events.contains(&StreamEvent::TextDelta {
+            index: 0,
+            text: "Hi".into()
+        }));
+        assert!(events.contains(&StreamEvent::BlockStart {
+            index: 1,
+            kind: BlockKind::ToolUse {
+                id: "call_1".into(),
+                name: "search".into()
+            }
+        }));
+        assert!(events.contains(&StreamEvent::Usage {
+            usage: Usage {
+                input_tokens: 3,
+                output_tokens: 4,
+                ..Usage::default()
+            }
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&StreamEvent::Done {
+                stop_reason: Some(StopReason::ToolUse)
+            })
+        );
+    }
+
+    #[test]
+    fn transcodes_anthropic_stream_to_openai_chunks() {
+        let events = transcode(
+            Provider::Anthropic,
+            Provider::OpenAiCompatible,
+            &[
+                (
+                    "message_start",
+                    r#"{"message":{"id":"msg_1","model":"claude","usage":{"input_tokens":5}}}"#,
+                ),
+                (
+                    "content_block_start",
+                    r#"{"index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+                ),
+                (
+                    "content_block_delta",
+                    r#"{"index":0,"delta":{"type":"thinking_delta","thinking":"hmm"}}"#,
+                ),
+                (
+                    "content_block_delta",
+                    r#"{"index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#,
+                ),
+                ("content_block_stop", r#"{"index":0}"#),
+                (
+                    "content_block_start",
+                    r#"{"index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"search","input":{}}}"#,
+                ),
+                (
+                    "content_block_delta",
+                    r#"{"index":1,"delta":{"type":"input_json_delta","partial_json":"{\"q\":1}"}}"#,
+                ),
+                ("content_block_stop", r#"{"index":1}"#),
+                (
+                    "message_delta",
+                    r#"{"delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":9}}"#,
+                ),
+                ("message_stop", r#"{}"#),
+            ],
+        );
+        assert!(events.contains(&StreamEvent::ThinkingSignature {
+            index: 0,
+            signature: "sig".into()
+        }));
+        assert!(events.contains(&StreamEvent::ToolCallDelta {
+            index: 1,
+            arguments: r#"{"q":1}"#.into()
+        }));
+        assert!(events.contains(&StreamEvent::Usage {
+            usage: Usage {
+                input_tokens: 5,
+                output_tokens: 9,
+                ..Usage::default()
+            }
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&StreamEvent::Done {
+                stop_reason: Some(StopReason::ToolUse)
+            })
+        );
+    }
+}
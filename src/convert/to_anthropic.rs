@@ -1,6 +1,8 @@
 use serde_json::{Value, json};
 
-use crate::types::openai::{InboundChatRequest, InboundContent, InboundContentPart};
+use crate::types::anthropic::{ContentBlock, DocumentSource};
+use crate::types::common::ToolChoice;
+use crate::types::openai::{FileData, InboundChatRequest, InboundContent, InboundContentPart};
 
 /// Convert an inbound OpenAI chat request to an Anthropic request body (as JSON Value).
 ///
@@ -10,6 +12,8 @@ use crate::types::openai::{InboundChatRequest, InboundContent, InboundContentPar
 /// - tool_calls in assistant messages → tool_use content blocks
 /// - role:"tool" messages → user messages with tool_result content blocks
 /// - Tool definition format conversion
+/// - `tool_choice` / `parallel_tool_calls` → Anthropic `tool_choice`
+/// - `stop` → `stop_sequences`, `user` → `metadata.user_id`
 pub fn inbound_request_to_anthropic(req: InboundChatRequest) -> Value {
     let mut system_parts: Vec<Value> = Vec::new();
     let mut messages: Vec<Value> = Vec::new();
@@ -35,8 +39,22 @@ pub fn inbound_request_to_anthropic(req: InboundChatRequest) -> Value {
             }
             "tool" => {
                 let content = match msg.content {
-                    InboundContent::Text(t) => t,
-                    _ => String::new(),
+                    InboundContent::Text(t) => json!(t),
+                    InboundContent::Parts(parts) => json!(
+                        parts
+                            .into_iter()
+                            .filter_map(|p| match p {
+                                InboundContentPart::Text { text } => {
+                                    Some(json!({"type": "text", "text": text}))
+                                }
+                                InboundContentPart::ImageUrl { image_url } => {
+                                    image_url_to_anthropic(&image_url.url)
+                                }
+                                InboundContentPart::File { .. } => None,
+                            })
+                            .collect::<Vec<_>>()
+                    ),
+                    InboundContent::Null => json!(""),
                 };
                 let tool_call_id = msg.tool_call_id.unwrap_or_default();
                 messages.push(json!({
@@ -63,22 +81,10 @@ pub fn inbound_request_to_anthropic(req: InboundChatRequest) -> Value {
                                     content_blocks.push(json!({"type": "text", "text": text}));
                                 }
                                 InboundContentPart::ImageUrl { image_url } => {
-                                    // Pass through as image block
-                                    if image_url.url.starts_with("data:") {
-                                        // Base64 encoded image
-                                        if let Some((media_type, data)) =
-                                            parse_data_url(&image_url.url)
-                                        {
-                                            content_blocks.push(json!({
-                                                "type": "image",
-                                                "source": {
-                                                    "type": "base64",
-                                                    "media_type": media_type,
-                                                    "data": data
-                                                }
-                                            }));
-                                        }
-                                    }
+                                    content_blocks.extend(image_url_to_anthropic(&image_url.url));
+                                }
+                                InboundContentPart::File { file } => {
+                                    content_blocks.extend(file_to_anthropic(file));
                                 }
                             }
                         }
@@ -117,21 +123,9 @@ pub fn inbound_request_to_anthropic(req: InboundChatRequest) -> Value {
                                 Some(json!({"type": "text", "text": text}))
                             }
                             InboundContentPart::ImageUrl { image_url } => {
-                                if image_url.url.starts_with("data:") {
-                                    parse_data_url(&image_url.url).map(|(media_type, data)| {
-                                        json!({
-                                            "type": "image",
-                                            "source": {
-                                                "type": "base64",
-                                                "media_type": media_type,
-                                                "data": data
-                                            }
-                                        })
-                                    })
-                                } else {
-                                    None
-                                }
+                                image_url_to_anthropic(&image_url.url)
                             }
+                            InboundContentPart::File { file } => file_to_anthropic(file),
                         })
                         .collect(),
                     InboundContent::Null => vec![json!({"type": "text", "text": ""})],
@@ -160,16 +154,40 @@ pub fn inbound_request_to_anthropic(req: InboundChatRequest) -> Value {
     if let Some(temperature) = req.temperature {
         body["temperature"] = json!(temperature);
     }
+    if let Some(top_p) = req.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    match req.stop {
+        Some(Value::String(stop)) => body["stop_sequences"] = json!([stop]),
+        Some(stop @ Value::Array(_)) => body["stop_sequences"] = stop,
+        _ => {}
+    }
+    if let Some(user) = req.user {
+        body["metadata"] = json!({"user_id": user});
+    }
     if !system_parts.is_empty() {
         body["system"] = json!(system_parts);
     }
 
+    // Anthropic rejects `tool_choice` on requests without tools.
+    let has_tools = req.tools.as_ref().is_some_and(|tools| !tools.is_empty());
+    let disable_parallel_tool_use = req.parallel_tool_calls == Some(false) && has_tools;
+
     // Convert tools
     if let Some(tools) = req.tools {
         let anthropic_tools: Vec<Value> = tools.into_iter().map(openai_tool_to_anthropic).collect();
         body["tools"] = json!(anthropic_tools);
     }
 
+    match req.tool_choice.as_ref().and_then(ToolChoice::from_openai) {
+        _ if !has_tools => {}
+        Some(choice) => body["tool_choice"] = choice.to_anthropic(disable_parallel_tool_use),
+        None if disable_parallel_tool_use => {
+            body["tool_choice"] = ToolChoice::Auto.to_anthropic(true);
+        }
+        None => {}
+    }
+
     body
 }
 
@@ -197,6 +215,41 @@ pub fn openai_tool_to_anthropic(tool: Value) -> Value {
     }
 }
 
+/// Convert an OpenAI `image_url` (data URL or http(s) URL) to an Anthropic image block.
+fn image_url_to_anthropic(url: &str) -> Option<Value> {
+    let block = if let Some((media_type, data)) = parse_data_url(url) {
+        ContentBlock::image_base64(media_type, data)
+    } else if url.starts_with("https://") || url.starts_with("http://") {
+        ContentBlock::image_url(url)
+    } else {
+        return None;
+    };
+    serde_json::to_value(block).ok()
+}
+
+/// Convert an OpenAI `file` part to an Anthropic document block.
+///
+/// Only PDFs are representable; other media types are dropped.
+fn file_to_anthropic(file: FileData) -> Option<Value> {
+    let file_data = file.file_data?;
+    let source = if let Some((media_type, data)) = parse_data_url(&file_data) {
+        if media_type != "application/pdf" {
+            return None;
+        }
+        DocumentSource::Base64 { media_type, data }
+    } else if file_data.starts_with("https://") || file_data.starts_with("http://") {
+        DocumentSource::Url { url: file_data }
+    } else {
+        return None;
+    };
+    serde_json::to_value(ContentBlock::Document {
+        source,
+        title: file.filename,
+        extra: serde_json::Map::new(),
+    })
+    .ok()
+}
+
 /// Parse a data URL into (media_type, base64_data).
 fn parse_data_url(url: &str) -> Option<(String, String)> {
     let rest = url.strip_prefix("data:")?;
@@ -204,3 +257,53 @@ fn parse_data_url(url: &str) -> Option<(String, String)> {
     let media_type = header.strip_suffix(";base64")?;
     Some((media_type.to_string(), data.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inbound(value: Value) -> InboundChatRequest {
+        serde_json::from_value(value).expect("inbound request")
+    }
+
+    #[test]
+    fn maps_tool_choice_and_parallel_tool_calls() {
+        let body = inbound_request_to_anthropic(inbound(json!({
+            "messages": [{ "role": "user", "content": "hi" }],
+            "tools": [{ "type": "function", "function": { "name": "search" } }],
+            "tool_choice": { "type": "function", "function": { "name": "search" } },
+            "parallel_tool_calls": false
+        })));
+        assert_eq!(
+            body["tool_choice"],
+            json!({ "type": "tool", "name": "search", "disable_parallel_tool_use": true })
+        );
+
+        let body = inbound_request_to_anthropic(inbound(json!({
+            "messages": [{ "role": "user", "content": "hi" }],
+            "tools": [{ "type": "function", "function": { "name": "search" } }],
+            "tool_choice": "required"
+        })));
+        assert_eq!(body["tool_choice"], json!({ "type": "any" }));
+
+        let body = inbound_request_to_anthropic(inbound(json!({
+            "messages": [{ "role": "user", "content": "hi" }],
+            "tool_choice": "required"
+        })));
+        assert!(body.get("tool_choice").is_none());
+
+        let body = inbound_request_to_anthropic(inbound(json!({
+            "messages": [{ "role": "user", "content": "hi" }],
+            "tools": [{ "type": "function", "function": { "name": "search" } }],
+            "tool_choice": "none",
+            "parallel_tool_calls": false
+        })));
+        assert_eq!(body["tool_choice"], json!({ "type": "none" }));
+
+        let body = inbound_request_to_anthropic(inbound(json!({
+            "messages": [{ "role": "user", "content": "hi" }],
+            "parallel_tool_calls": false
+        })));
+        assert!(body.get("tool_choice").is_none());
+    }
+}
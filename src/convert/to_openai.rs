@@ -1,17 +1,21 @@
 use tracing::warn;
 
-use crate::types::anthropic::{ContentBlock, Message, MessagesResponse};
-use crate::types::common::{StopReason, ToolDefinition};
+use crate::types::anthropic::{ContentBlock, DocumentSource, Message, MessagesResponse};
+use crate::types::common::{StopReason, ToolDefinition, Usage};
 use crate::types::openai::{
-    ChatMessage, ChatResponse, Choice, ResponseMessage, ResponseToolCall, ResponseToolCallFunction,
-    ResponseUsage, Tool, ToolCallFunction, ToolCallOut, ToolFunction,
+    ChatContent, ChatMessage, ChatResponse, Choice, ContentPart, FileData, ImageUrlData,
+    ReasoningDetail, ResponseMessage, ResponseToolCall, ResponseToolCallFunction, ResponseUsage,
+    Tool, ToolCallFunction, ToolCallOut, ToolFunction,
 };
 
 /// Convert Anthropic messages + system prompt to OpenAI message format.
 ///
 /// - System prompt becomes the first `role: "system"` message
 /// - Assistant text → `content`, ToolUse → `tool_calls` array
-/// - User messages with ToolResult → multiple `role: "tool"` messages
+/// - User messages with ToolResult → multiple `role: "tool"` messages with the
+///   text of each result; images/documents follow in one `role: "user"` message
+/// - User images → `image_url` parts, PDF documents → `file` parts,
+///   plain-text documents → `text` parts
 /// - Thinking blocks are silently skipped
 pub fn messages_to_openai(system: Option<&str>, messages: &[Message]) -> Vec<ChatMessage> {
     let mut out = Vec::new();
@@ -26,7 +30,7 @@ pub fn messages_to_openai(system: Option<&str>, messages: &[Message]) -> Vec<Cha
                 .content
                 .iter()
                 .filter_map(|b| {
-                    if let ContentBlock::Text { text } = b {
+                    if let ContentBlock::Text { text, .. } = b {
                         Some(text.as_str())
                     } else {
                         None
@@ -38,7 +42,10 @@ pub fn messages_to_openai(system: Option<&str>, messages: &[Message]) -> Vec<Cha
                 .content
                 .iter()
                 .filter_map(|b| {
-                    if let ContentBlock::ToolUse { id, name, input } = b {
+                    if let ContentBlock::ToolUse {
+                        id, name, input, ..
+                    } = b
+                    {
                         Some(ToolCallOut {
                             id: id.clone(),
                             call_type: "function".to_string(),
@@ -56,7 +63,7 @@ pub fn messages_to_openai(system: Option<&str>, messages: &[Message]) -> Vec<Cha
             let content_str = if text_parts.is_empty() {
                 None
             } else {
-                Some(text_parts.join("\n"))
+                Some(ChatContent::Text(text_parts.join("\n")))
             };
 
             out.push(ChatMessage {
@@ -76,31 +83,30 @@ pub fn messages_to_openai(system: Option<&str>, messages: &[Message]) -> Vec<Cha
                 .any(|b| matches!(b, ContentBlock::ToolResult { .. }));
 
             if has_tool_results {
+                // Tool messages only carry text, so images and documents (and
+                // any text sent alongside the results) follow as a user message.
+                let mut follow_up: Vec<ContentBlock> = Vec::new();
                 for block in &msg.content {
-                    if let ContentBlock::ToolResult {
-                        tool_use_id,
-                        content,
-                        ..
-                    } = block
-                    {
-                        out.push(ChatMessage::tool_result(tool_use_id, content));
+                    match block {
+                        ContentBlock::ToolResult {
+                            tool_use_id,
+                            content,
+                            ..
+                        } => {
+                            out.push(ChatMessage::tool_result(tool_use_id, content.text()));
+                            follow_up.extend(content.attachments().into_iter().cloned());
+                        }
+                        ContentBlock::Text { .. }
+                        | ContentBlock::Image { .. }
+                        | ContentBlock::Document { .. } => follow_up.push(block.clone()),
+                        _ => {}
                     }
                 }
+                if !follow_up.is_empty() {
+                    out.push(user_message_to_openai(&follow_up));
+                }
             } else {
-                let text = msg
-                    .content
-                    .iter()
-                    .filter_map(|b| {
-                        if let ContentBlock::Text { text } = b {
-                            Some(text.as_str())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n");
-
-                out.push(ChatMessage::user(text));
+                out.push(user_message_to_openai(&msg.content));
             }
         }
     }
@@ -108,17 +114,98 @@ pub fn messages_to_openai(system: Option<&str>, messages: &[Message]) -> Vec<Cha
     out
 }
 
+/// Convert user content blocks to a single OpenAI user message.
+///
+/// Text-only content stays a plain string for maximum compatibility; any
+/// image or document switches the message to an array of content parts.
+fn user_message_to_openai(blocks: &[ContentBlock]) -> ChatMessage {
+    let multimodal = blocks.iter().any(|b| {
+        matches!(
+            b,
+            ContentBlock::Image { .. } | ContentBlock::Document { .. }
+        )
+    });
+
+    if !multimodal {
+        let text = blocks
+            .iter()
+            .filter_map(|b| {
+                if let ContentBlock::Text { text, .. } = b {
+                    Some(text.as_str())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        return ChatMessage::user(text);
+    }
+
+    let parts = blocks
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Text { text, .. } => Some(ContentPart::Text { text: text.clone() }),
+            ContentBlock::Image { source, .. } => Some(ContentPart::ImageUrl {
+                image_url: ImageUrlData {
+                    url: source.to_url(),
+                },
+            }),
+            ContentBlock::Document { source, title, .. } => Some(document_to_openai(source, title)),
+            _ => None,
+        })
+        .collect();
+    ChatMessage::user_parts(parts)
+}
+
+fn document_to_openai(source: &DocumentSource, title: &Option<String>) -> ContentPart {
+    match source {
+        DocumentSource::Base64 { media_type, data } => ContentPart::File {
+            file: FileData {
+                filename: Some(title.clone().unwrap_or_else(|| "document.pdf".to_string())),
+                file_data: Some(format!("data:{media_type};base64,{data}")),
+            },
+        },
+        // `file_data` only takes data URLs, so name the document in text.
+        DocumentSource::Url { url } => {
+            warn!("Document URLs are not supported in OpenAI format, sending {url} as text");
+            ContentPart::Text {
+                text: match title {
+                    Some(title) => format!("Document \"{title}\": {url}"),
+                    None => format!("Document: {url}"),
+                },
+            }
+        }
+        DocumentSource::Text { data, .. } => ContentPart::Text {
+            text: match title {
+                Some(title) => format!("{title}\n\n{data}"),
+                None => data.clone(),
+            },
+        },
+    }
+}
+
 /// Convert provider-agnostic ToolDefinitions to OpenAI tool format.
+///
+/// Anthropic server tools have no OpenAI equivalent and are skipped.
 pub fn tools_to_openai(tools: &[ToolDefinition]) -> Vec<Tool> {
     tools
         .iter()
-        .map(|t| Tool {
-            tool_type: "function".to_string(),
-            function: ToolFunction {
-                name: t.name.clone(),
-                description: t.description.clone(),
-                parameters: t.input_schema.clone(),
-            },
+        .filter_map(|t| match t {
+            ToolDefinition::Custom(t) => Some(Tool {
+                tool_type: "function".to_string(),
+                function: ToolFunction {
+                    name: t.name.clone(),
+                    description: t.description.clone(),
+                    parameters: t.input_schema.clone(),
+                },
+            }),
+            ToolDefinition::Server(t) => {
+                warn!(
+                    "Skipping server tool {} ({}) for OpenAI format",
+                    t.name, t.version
+                );
+                None
+            }
         })
         .collect()
 }
@@ -134,20 +221,44 @@ pub fn response_to_anthropic(resp: ChatResponse) -> Result<MessagesResponse, Str
         .next()
         .ok_or("OpenAI response had no choices")?;
 
-    let mut content: Vec<ContentBlock> = Vec::new();
-
-    if let Some(text) = choice.message.content
-        && !text.is_empty()
-    {
-        content.push(ContentBlock::Text { text });
-    }
+    // Thinking comes first, matching Anthropic's block order so the
+    // response can be replayed as an assistant turn.
+    let mut content: Vec<ContentBlock> = choice
+        .message
+        .reasoning_details
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|detail| match detail {
+            ReasoningDetail::Text { text, signature } => Some(ContentBlock::Thinking {
+                thinking: text,
+                signature,
+                extra: serde_json::Map::new(),
+            }),
+            ReasoningDetail::Encrypted { data } => Some(ContentBlock::RedactedThinking {
+                data,
+                extra: serde_json::Map::new(),
+            }),
+            ReasoningDetail::Other => None,
+        })
+        .collect();
 
-    if let Some(thinking) = choice.message.reasoning_content
+    if content.is_empty()
+        && let Some(thinking) = choice.message.reasoning_content
         && !thinking.is_empty()
     {
         content.push(ContentBlock::Thinking {
             thinking,
             signature: None,
+            extra: serde_json::Map::new(),
+        });
+    }
+
+    if let Some(text) = choice.message.content
+        && !text.is_empty()
+    {
+        content.push(ContentBlock::Text {
+            text,
+            extra: serde_json::Map::new(),
         });
     }
 
@@ -165,6 +276,7 @@ pub fn response_to_anthropic(resp: ChatResponse) -> Result<MessagesResponse, Str
                 id: tc.id,
                 name: tc.function.name,
                 input,
+                extra: serde_json::Map::new(),
             });
         }
     }
@@ -176,7 +288,7 @@ pub fn response_to_anthropic(resp: ChatResponse) -> Result<MessagesResponse, Str
         model: resp.model,
         content,
         stop_reason,
-        usage: resp.usage.map(|u| crate::types::common::Usage {
+        usage: resp.usage.map(|u| Usage {
             input_tokens: u.prompt_tokens,
             output_tokens: u.completion_tokens,
             cache_creation_input_tokens: u.cache_creation_input_tokens,
@@ -191,19 +303,53 @@ pub fn response_to_anthropic(resp: ChatResponse) -> Result<MessagesResponse, Str
     })
 }
 
+pub(crate) fn usage_to_openai(u: &Usage) -> ResponseUsage {
+    ResponseUsage {
+        prompt_tokens: u.input_tokens,
+        completion_tokens: u.output_tokens,
+        total_tokens: u.input_tokens + u.output_tokens,
+        cache_creation_input_tokens: u.cache_creation_input_tokens,
+        cache_read_input_tokens: u.cache_read_input_tokens,
+        cost: u.cost,
+        completion_tokens_details: (u.reasoning_tokens > 0).then_some(
+            crate::types::openai::CompletionTokensDetails {
+                reasoning_tokens: u.reasoning_tokens,
+            },
+        ),
+    }
+}
+
 /// Convert an Anthropic MessagesResponse to an OpenAI ChatResponse.
 ///
 /// This is for proxy scenarios — Anthropic response → OpenAI format out.
+/// Thinking signatures and redacted thinking are kept in `reasoning_details`
+/// so [`response_to_anthropic`] can restore them for the next turn.
 pub fn anthropic_response_to_openai(resp: MessagesResponse) -> ChatResponse {
     let mut text_parts = Vec::new();
     let mut reasoning_parts = Vec::new();
+    let mut reasoning_details = Vec::new();
     let mut tool_calls = Vec::new();
 
     for block in &resp.content {
         match block {
-            ContentBlock::Text { text } => text_parts.push(text.as_str()),
-            ContentBlock::Thinking { thinking, .. } => reasoning_parts.push(thinking.as_str()),
-            ContentBlock::ToolUse { id, name, input } => {
+            ContentBlock::Text { text, .. } => text_parts.push(text.as_str()),
+            ContentBlock::Thinking {
+                thinking,
+                signature,
+                ..
+            } => {
+                reasoning_parts.push(thinking.as_str());
+                reasoning_details.push(ReasoningDetail::Text {
+                    text: thinking.clone(),
+                    signature: signature.clone(),
+                });
+            }
+            ContentBlock::RedactedThinking { data, .. } => {
+                reasoning_details.push(ReasoningDetail::Encrypted { data: data.clone() });
+            }
+            ContentBlock::ToolUse {
+                id, name, input, ..
+            } => {
                 tool_calls.push(ResponseToolCall {
                     id: id.clone(),
                     call_type: Some("function".to_string()),
@@ -231,19 +377,7 @@ pub fn anthropic_response_to_openai(resp: MessagesResponse) -> ChatResponse {
 
     let finish_reason = resp.stop_reason.to_openai();
 
-    let usage = resp.usage.map(|u| ResponseUsage {
-        prompt_tokens: u.input_tokens,
-        completion_tokens: u.output_tokens,
-        total_tokens: u.input_tokens + u.output_tokens,
-        cache_creation_input_tokens: u.cache_creation_input_tokens,
-        cache_read_input_tokens: u.cache_read_input_tokens,
-        cost: u.cost,
-        completion_tokens_details: (u.reasoning_tokens > 0).then_some(
-            crate::types::openai::CompletionTokensDetails {
-                reasoning_tokens: u.reasoning_tokens,
-            },
-        ),
-    });
+    let usage = resp.usage.as_ref().map(usage_to_openai);
 
     ChatResponse {
         id: resp.id.clone(),
@@ -261,6 +395,20 @@ pub fn anthropic_response_to_openai(resp: MessagesResponse) -> ChatResponse {
                 role: Some("assistant".to_string()),
                 content,
                 reasoning_content,
+                // Only needed when there is something opaque to preserve.
+                reasoning_details: reasoning_details
+                    .iter()
+                    .any(|detail| {
+                        matches!(
+                            detail,
+                            ReasoningDetail::Encrypted { .. }
+                                | ReasoningDetail::Text {
+                                    signature: Some(_),
+                                    ..
+                                }
+                        )
+                    })
+                    .then_some(reasoning_details),
                 tool_calls: if tool_calls.is_empty() {
                     None
                 } else {
@@ -310,4 +458,103 @@ mod tests {
         assert_eq!(usage.reasoning_tokens, 4);
         assert_eq!(usage.cost, Some(0.001));
     }
+
+    #[test]
+    fn round_trips_signatures_and_redacted_thinking_through_openai_format() {
+        let response: MessagesResponse = serde_json::from_value(serde_json::json!({
+            "id": "msg_1",
+            "model": "claude",
+            "content": [
+                { "type": "thinking", "thinking": "step one", "signature": "sig" },
+                { "type": "redacted_thinking", "data": "opaque" },
+                { "type": "text", "text": "done" }
+            ],
+            "stop_reason": "end_turn"
+        }))
+        .expect("response with redacted thinking");
+
+        let openai = anthropic_response_to_openai(response);
+        let message = &openai.choices[0].message;
+        assert_eq!(message.reasoning_content.as_deref(), Some("step one"));
+        assert_eq!(message.reasoning_details.as_ref().map(Vec::len), Some(2));
+
+        let restored = response_to_anthropic(openai).expect("canonical response");
+        assert!(matches!(
+            &restored.content[0],
+            ContentBlock::Thinking { signature: Some(signature), .. } if signature == "sig"
+        ));
+        assert!(matches!(
+            &restored.content[1],
+            ContentBlock::RedactedThinking { data, .. } if data == "opaque"
+        ));
+        assert_eq!(restored.text(), "done");
+    }
+
+    #[test]
+    fn converts_images_and_documents_to_content_parts() {
+        let messages = [Message::user(vec![
+            ContentBlock::text("Describe these"),
+            ContentBlock::image_base64("image/png", "iVBORw0"),
+            ContentBlock::image_url("https://example.com/cat.jpg"),
+            ContentBlock::pdf_base64("JVBERi0"),
+            ContentBlock::pdf_url("https://example.com/report.pdf"),
+        ])];
+        let converted =
+            serde_json::to_value(messages_to_openai(None, &messages)).expect("serialize messages");
+        assert_eq!(
+            converted[0]["content"],
+            serde_json::json!([
+                { "type": "text", "text": "Describe these" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0" } },
+                { "type": "image_url", "image_url": { "url": "https://example.com/cat.jpg" } },
+                {
+                    "type": "file",
+                    "file": {
+                        "filename": "document.pdf",
+                        "file_data": "data:application/pdf;base64,JVBERi0"
+                    }
+                },
+                { "type": "text", "text": "Document: https://example.com/report.pdf" }
+            ])
+        );
+
+        let text_only =
+            serde_json::to_value(messages_to_openai(None, &[Message::user_text("plain")]))
+                .expect("serialize messages");
+        assert_eq!(text_only[0]["content"], "plain");
+    }
+
+    #[test]
+    fn forwards_tool_result_images_as_follow_up_user_message() {
+        let result: ContentBlock = serde_json::from_value(serde_json::json!({
+            "type": "tool_result",
+            "tool_use_id": "call_1",
+            "content": [
+                { "type": "text", "text": "screenshot taken" },
+                {
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": "image/png", "data": "iVBORw0" }
+                }
+            ]
+        }))
+        .expect("tool result with blocks");
+        let converted = serde_json::to_value(messages_to_openai(
+            None,
+            &[Message::tool_results(vec![
+                result,
+                ContentBlock::tool_result("call_2", "plain", false),
+            ])],
+        ))
+        .expect("serialize messages");
+
+        assert_eq!(converted[0]["role"], "tool");
+        assert_eq!(converted[0]["content"], "screenshot taken");
+        assert_eq!(converted[1]["tool_call_id"], "call_2");
+        assert_eq!(converted[1]["content"], "plain");
+        assert_eq!(converted[2]["role"], "user");
+        assert_eq!(
+            converted[2]["content"][0]["image_url"]["url"],
+            "data:image/png;base64,iVBORw0"
+        );
+    }
 }
@@ -5,21 +5,30 @@ pub mod types;
 pub mod client;
 
 // Re-export commonly used types at crate root
-pub use types::anthropic::{ContentBlock, Message, MessagesResponse};
+pub use types::anthropic::{ContentBlock, Message, MessagesResponse, ToolResultContent};
 pub use types::common::{
-    EffortLevel, Provider, ResponseFormat, StopReason, ThinkingConfig, ToolDefinition, Usage,
+    CustomTool, EffortLevel, Provider, ResponseFormat, ServerTool, StopReason, ThinkingConfig,
+    ToolChoice, ToolDefinition, Usage,
 };
+pub use types::conversation::Conversation;
+pub use types::stream::{BlockKind, StreamEvent};
 
 #[cfg(feature = "client")]
 pub use client::{
-    AuthScheme, ChatOptions, ClientConfig, LlmClient, LlmError, RetryPolicy, StructuredResponse,
+    ApiErrorKind, AuthScheme, Capabilities, ChatOptions, ClientConfig, ContextManager,
+    HeuristicTokenizer, Jitter, LlmClient, LlmError, ModelInfo, RateLimitInfo, RetryPolicy,
+    StructuredAttempt, StructuredResponse, TokenCount, Tokenizer, Tool, ToolRegistry, ToolRun,
+    TransportErrorKind, TransportRetries, UnsupportedParamPolicy,
 };
 
 #[cfg(feature = "streaming")]
-pub use client::{ChatStream, StreamEvent};
+pub use client::{
+    AccumulatingStream, CancelHandle, ChatStream, StreamAccumulator, StructuredEvent,
+    StructuredStream, ToolCallTracker,
+};
 
 #[cfg(feature = "rig")]
 pub use client::rig::RigClient;
 
 #[cfg(feature = "embeddings")]
-pub use client::{EmbeddingsClient, EmbeddingsConfig};
+pub use client::{EmbeddingsClient, EmbeddingsConfig, EmbeddingsOptions};
@@ -1,19 +1,39 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::common::{StopReason, ToolDefinition, Usage};
 
 /// Anthropic content block — the canonical internal representation.
+///
+/// Block types without a dedicated variant deserialize into
+/// [`ContentBlock::Unknown`] instead of failing the whole message; a known
+/// type with invalid fields is still an error. Fields of known types that
+/// have no dedicated field (`citations`, `cache_control`, ...) are kept in
+/// `extra` so they survive a round trip too.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
+#[serde(tag = "type", remote = "Self")]
 pub enum ContentBlock {
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        text: String,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
 
     #[serde(rename = "thinking")]
     Thinking {
         thinking: String,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         signature: Option<String>,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+
+    /// Encrypted thinking that must be replayed verbatim on the next turn.
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking {
+        data: String,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
     },
 
     #[serde(rename = "tool_use")]
@@ -21,20 +41,226 @@ pub enum ContentBlock {
         id: String,
         name: String,
         input: serde_json::Value,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
     },
 
     #[serde(rename = "tool_result")]
     ToolResult {
         tool_use_id: String,
-        content: String,
+        #[serde(default)]
+        content: ToolResultContent,
         #[serde(default, skip_serializing_if = "std::ops::Not::not")]
         is_error: bool,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
     },
+
+    /// Call of an Anthropic server tool; executed by the provider.
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult {
+        tool_use_id: String,
+        content: WebSearchToolResultContent,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+
+    #[serde(rename = "image")]
+    Image {
+        source: ImageSource,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+
+    #[serde(rename = "document")]
+    Document {
+        source: DocumentSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(flatten, default, skip_serializing_if = "serde_json::Map::is_empty")]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+
+    /// Any block this crate does not model yet, kept verbatim so it
+    /// survives a deserialize/serialize round trip.
+    #[serde(skip)]
+    Unknown(serde_json::Value),
+}
+
+/// `type` tags with a dedicated [`ContentBlock`] variant.
+const KNOWN_BLOCK_TYPES: &[&str] = &[
+    "text",
+    "thinking",
+    "redacted_thinking",
+    "tool_use",
+    "tool_result",
+    "server_tool_use",
+    "web_search_tool_result",
+    "image",
+    "document",
+];
+
+impl Serialize for ContentBlock {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Unknown(value) => value.serialize(serializer),
+            _ => Self::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentBlock {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        match value.get("type").and_then(serde_json::Value::as_str) {
+            Some(kind) if KNOWN_BLOCK_TYPES.contains(&kind) => {
+                Self::deserialize(value).map_err(serde::de::Error::custom)
+            }
+            _ => Ok(Self::Unknown(value)),
+        }
+    }
+}
+
+/// Content of a `tool_result` block: a plain string or an array of
+/// content blocks (text and images, matching the Anthropic wire format).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolResultContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl ToolResultContent {
+    /// Extract all text content concatenated.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Non-text blocks (images, documents) carried by the result.
+    pub fn attachments(&self) -> Vec<&ContentBlock> {
+        match self {
+            Self::Text(_) => Vec::new(),
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .filter(|b| {
+                    matches!(
+                        b,
+                        ContentBlock::Image { .. } | ContentBlock::Document { .. }
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Default for ToolResultContent {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+impl From<String> for ToolResultContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for ToolResultContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentBlock>> for ToolResultContent {
+    fn from(blocks: Vec<ContentBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+/// Content of a `web_search_tool_result` block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WebSearchToolResultContent {
+    Results(Vec<WebSearchResult>),
+    Error(WebSearchToolResultError),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "web_search_result")]
+pub struct WebSearchResult {
+    pub url: String,
+    pub title: String,
+    /// Opaque page content; must be passed back unchanged in later turns.
+    pub encrypted_content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_age: Option<String>,
+}
+
+/// Web search failure, e.g. `error_code: "max_uses_exceeded"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "web_search_tool_result_error")]
+pub struct WebSearchToolResultError {
+    pub error_code: String,
+}
+
+/// Image source for an `image` content block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ImageSource {
+    /// Inline base64 image data, e.g. `media_type: "image/png"`.
+    Base64 { media_type: String, data: String },
+    /// Publicly reachable image URL.
+    Url { url: String },
+}
+
+impl ImageSource {
+    /// Render the source as a URL, using a `data:` URL for inline images.
+    pub fn to_url(&self) -> String {
+        match self {
+            Self::Base64 { media_type, data } => format!("data:{media_type};base64,{data}"),
+            Self::Url { url } => url.clone(),
+        }
+    }
+}
+
+/// Document source for a `document` content block.
+///
+/// - `Base64`: inline PDF (`media_type: "application/pdf"`).
+/// - `Text`: inline plain text (`media_type: "text/plain"`).
+/// - `Url`: publicly reachable PDF URL.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DocumentSource {
+    Base64 { media_type: String, data: String },
+    Text { media_type: String, data: String },
+    Url { url: String },
 }
 
 impl ContentBlock {
     pub fn text(text: impl Into<String>) -> Self {
-        Self::Text { text: text.into() }
+        Self::Text {
+            text: text.into(),
+            extra: serde_json::Map::new(),
+        }
     }
 
     pub fn tool_use(
@@ -46,18 +272,21 @@ impl ContentBlock {
             id: id.into(),
             name: name.into(),
             input,
+            extra: serde_json::Map::new(),
         }
     }
 
+    /// Tool result with string content or an array of text/image blocks.
     pub fn tool_result(
         tool_use_id: impl Into<String>,
-        content: impl Into<String>,
+        content: impl Into<ToolResultContent>,
         is_error: bool,
     ) -> Self {
         Self::ToolResult {
             tool_use_id: tool_use_id.into(),
             content: content.into(),
             is_error,
+            extra: serde_json::Map::new(),
         }
     }
 
@@ -65,6 +294,57 @@ impl ContentBlock {
         Self::Thinking {
             thinking: thinking.into(),
             signature,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Inline base64 image, e.g. `image_base64("image/png", data)`.
+    pub fn image_base64(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        Self::Image {
+            source: ImageSource::Base64 {
+                media_type: media_type.into(),
+                data: data.into(),
+            },
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    pub fn image_url(url: impl Into<String>) -> Self {
+        Self::Image {
+            source: ImageSource::Url { url: url.into() },
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Inline base64-encoded PDF document.
+    pub fn pdf_base64(data: impl Into<String>) -> Self {
+        Self::Document {
+            source: DocumentSource::Base64 {
+                media_type: "application/pdf".to_string(),
+                data: data.into(),
+            },
+            title: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    pub fn pdf_url(url: impl Into<String>) -> Self {
+        Self::Document {
+            source: DocumentSource::Url { url: url.into() },
+            title: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Inline plain-text document.
+    pub fn text_document(text: impl Into<String>, title: Option<String>) -> Self {
+        Self::Document {
+            source: DocumentSource::Text {
+                media_type: "text/plain".to_string(),
+                data: text.into(),
+            },
+            title,
+            extra: serde_json::Map::new(),
         }
     }
 }
@@ -122,6 +402,12 @@ pub struct OutputConfig {
     pub effort: String,
 }
 
+/// Request metadata (`metadata.user_id` identifies the end user).
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestMetadata {
+    pub user_id: String,
+}
+
 /// Anthropic Messages API request.
 #[derive(Debug, Serialize)]
 pub struct MessagesRequest {
@@ -131,6 +417,16 @@ pub struct MessagesRequest {
     pub system: Option<String>,
     pub messages: Vec<Message>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<RequestMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ToolDefinition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub thinking: Option<ThinkingParam>,
@@ -141,25 +437,56 @@ pub struct MessagesRequest {
 }
 
 /// Anthropic Messages API response.
-#[derive(Debug, Deserialize)]
+///
+/// The wire-level `stop_sequence` field is folded into
+/// [`StopReason::StopSequence`].
+#[derive(Debug)]
 pub struct MessagesResponse {
-    #[serde(default)]
     pub id: Option<String>,
-    #[serde(default)]
     pub model: Option<String>,
     pub content: Vec<ContentBlock>,
     pub stop_reason: StopReason,
-    #[serde(default)]
     pub usage: Option<Usage>,
 }
 
+impl<'de> Deserialize<'de> for MessagesResponse {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Wire {
+            #[serde(default)]
+            id: Option<String>,
+            #[serde(default)]
+            model: Option<String>,
+            content: Vec<ContentBlock>,
+            stop_reason: StopReason,
+            #[serde(default)]
+            stop_sequence: Option<String>,
+            #[serde(default)]
+            usage: Option<Usage>,
+        }
+
+        let wire = Wire::deserialize(deserializer)?;
+        let stop_reason = match wire.stop_reason {
+            StopReason::StopSequence(None) => StopReason::StopSequence(wire.stop_sequence),
+            other => other,
+        };
+        Ok(Self {
+            id: wire.id,
+            model: wire.model,
+            content: wire.content,
+            stop_reason,
+            usage: wire.usage,
+        })
+    }
+}
+
 impl MessagesResponse {
     /// Extract all text content concatenated.
     pub fn text(&self) -> String {
         self.content
             .iter()
             .filter_map(|b| match b {
-                ContentBlock::Text { text } => Some(text.as_str()),
+                ContentBlock::Text { text, .. } => Some(text.as_str()),
                 _ => None,
             })
             .collect::<Vec<_>>()
@@ -196,3 +523,138 @@ impl MessagesResponse {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_unknown_content_blocks_verbatim() {
+        let block = serde_json::json!({
+            "type": "container_upload",
+            "file_id": "file_1",
+            "cache_control": { "type": "ephemeral" }
+        });
+        let response: MessagesResponse = serde_json::from_value(serde_json::json!({
+            "content": [block.clone(), { "type": "text", "text": "hi" }],
+            "stop_reason": "end_turn"
+        }))
+        .expect("response with unknown block");
+
+        assert!(matches!(&response.content[0], ContentBlock::Unknown(value) if *value == block));
+        assert_eq!(response.text(), "hi");
+        assert_eq!(
+            serde_json::to_value(&response.content[0]).expect("serialize block"),
+            block
+        );
+    }
+
+    #[test]
+    fn keeps_unmodelled_fields_on_known_blocks() {
+        let block = serde_json::json!({
+            "type": "text",
+            "text": "Paris",
+            "citations": [{
+                "type": "web_search_result_location",
+                "url": "https://example.com",
+                "title": "Example",
+                "cited_text": "Paris is the capital",
+                "encrypted_index": "abc"
+            }],
+            "cache_control": { "type": "ephemeral" }
+        });
+        let parsed: ContentBlock = serde_json::from_value(block.clone()).expect("text block");
+
+        let ContentBlock::Text { text, extra } = &parsed else {
+            panic!("expected a text block, got {parsed:?}");
+        };
+        assert_eq!(text, "Paris");
+        assert!(extra.contains_key("citations"));
+        assert_eq!(
+            serde_json::to_value(&parsed).expect("serialize block"),
+            block
+        );
+    }
+
+    #[test]
+    fn rejects_known_blocks_with_invalid_fields() {
+        let error = serde_json::from_value::<ContentBlock>(serde_json::json!({
+            "type": "tool_use",
+            "id": "x",
+            "name": "y"
+        }))
+        .unwrap_err();
+        assert!(error.to_string().contains("input"), "{error}");
+
+        let block = ContentBlock::tool_use("x", "y", serde_json::json!({}));
+        let value = serde_json::to_value(&block).expect("serialize block");
+        assert_eq!(value["type"], "tool_use");
+        assert!(matches!(
+            serde_json::from_value(value).expect("round trip"),
+            ContentBlock::ToolUse { .. }
+        ));
+    }
+
+    #[test]
+    fn folds_matched_stop_sequence_into_stop_reason() {
+        let response: MessagesResponse = serde_json::from_value(serde_json::json!({
+            "content": [{ "type": "text", "text": "1, 2, 3" }],
+            "stop_reason": "stop_sequence",
+            "stop_sequence": "4"
+        }))
+        .expect("response");
+        assert_eq!(
+            response.stop_reason,
+            StopReason::StopSequence(Some("4".to_string()))
+        );
+        assert_eq!(response.stop_reason.to_openai(), "stop");
+    }
+
+    #[test]
+    fn parses_server_tool_use_and_web_search_results() {
+        let content = serde_json::json!([
+            {
+                "type": "server_tool_use",
+                "id": "srvtoolu_1",
+                "name": "web_search",
+                "input": { "query": "rust serde" }
+            },
+            {
+                "type": "web_search_tool_result",
+                "tool_use_id": "srvtoolu_1",
+                "content": [{
+                    "type": "web_search_result",
+                    "url": "https://serde.rs",
+                    "title": "Serde",
+                    "encrypted_content": "abc"
+                }]
+            },
+            {
+                "type": "web_search_tool_result",
+                "tool_use_id": "srvtoolu_2",
+                "content": { "type": "web_search_tool_result_error", "error_code": "max_uses_exceeded" }
+            }
+        ]);
+        let blocks: Vec<ContentBlock> =
+            serde_json::from_value(content.clone()).expect("server tool blocks");
+
+        assert!(
+            matches!(&blocks[0], ContentBlock::ServerToolUse { name, .. } if name == "web_search")
+        );
+        assert!(matches!(
+            &blocks[1],
+            ContentBlock::WebSearchToolResult {
+                content: WebSearchToolResultContent::Results(results),
+                ..
+            } if results[0].url == "https://serde.rs"
+        ));
+        assert!(matches!(
+            &blocks[2],
+            ContentBlock::WebSearchToolResult {
+                content: WebSearchToolResultContent::Error(_),
+                ..
+            }
+        ));
+        assert_eq!(serde_json::to_value(&blocks).expect("serialize"), content);
+    }
+}
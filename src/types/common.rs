@@ -111,23 +111,196 @@ impl std::str::FromStr for EffortLevel {
 }
 
 /// Provider-agnostic tool definition.
+///
+/// - `Custom`: a function tool executed by the caller, described by a JSON Schema.
+/// - `Server`: a tool hosted and executed by Anthropic (web search, code
+///   execution, web fetch). Only the native Anthropic transport supports these.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolDefinition {
-    pub name: String,
-    pub description: String,
-    pub input_schema: serde_json::Value,
+#[serde(untagged)]
+pub enum ToolDefinition {
+    Custom(CustomTool),
+    Server(ServerTool),
 }
 
 impl ToolDefinition {
+    /// Create a custom function tool.
     pub fn new(
         name: impl Into<String>,
         description: impl Into<String>,
         input_schema: serde_json::Value,
     ) -> Self {
-        Self {
+        Self::Custom(CustomTool {
             name: name.into(),
             description: description.into(),
             input_schema,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Custom(tool) => &tool.name,
+            Self::Server(tool) => &tool.name,
+        }
+    }
+
+    pub fn is_server(&self) -> bool {
+        matches!(self, Self::Server(_))
+    }
+}
+
+impl From<CustomTool> for ToolDefinition {
+    fn from(tool: CustomTool) -> Self {
+        Self::Custom(tool)
+    }
+}
+
+impl From<ServerTool> for ToolDefinition {
+    fn from(tool: ServerTool) -> Self {
+        Self::Server(tool)
+    }
+}
+
+/// Client-executed function tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub input_schema: serde_json::Value,
+}
+
+/// Anthropic server tool, identified by its versioned `type`.
+///
+/// Code execution and web fetch additionally require the matching
+/// `anthropic-beta` header (`code-execution-2025-08-25`,
+/// `web-fetch-2025-09-10`), which can be set with `ClientConfig::header`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerTool {
+    /// Versioned tool type, e.g. `web_search_20250305`.
+    #[serde(rename = "type")]
+    pub version: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+    /// Other tool-specific settings (`user_location`, `citations`, ...).
+    #[serde(flatten)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ServerTool {
+    pub fn new(version: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            name: name.into(),
+            max_uses: None,
+            allowed_domains: None,
+            blocked_domains: None,
+            config: serde_json::Map::new(),
+        }
+    }
+
+    pub fn web_search() -> Self {
+        Self::new("web_search_20250305", "web_search")
+    }
+
+    pub fn web_fetch() -> Self {
+        Self::new("web_fetch_20250910", "web_fetch")
+    }
+
+    pub fn code_execution() -> Self {
+        Self::new("code_execution_20250825", "code_execution")
+    }
+
+    #[must_use]
+    pub fn max_uses(mut self, max_uses: u32) -> Self {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    #[must_use]
+    pub fn allowed_domains(mut self, domains: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    #[must_use]
+    pub fn blocked_domains(mut self, domains: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.blocked_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set any other tool-specific field.
+    #[must_use]
+    pub fn option(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.config.insert(key.into(), value);
+        self
+    }
+}
+
+/// Tool selection policy, normalized across providers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// The model decides whether to call tools.
+    Auto,
+    /// The model must call at least one tool (OpenAI `required`).
+    Any,
+    /// The model must not call tools.
+    None,
+    /// The model must call the named tool.
+    Tool(String),
+}
+
+impl ToolChoice {
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self::Tool(name.into())
+    }
+
+    /// Anthropic `tool_choice` object. `disable_parallel_tool_use` is ignored
+    /// for `None`, which does not accept it.
+    pub fn to_anthropic(&self, disable_parallel_tool_use: bool) -> serde_json::Value {
+        let mut value = match self {
+            Self::Auto => serde_json::json!({"type": "auto"}),
+            Self::Any => serde_json::json!({"type": "any"}),
+            Self::None => return serde_json::json!({"type": "none"}),
+            Self::Tool(name) => serde_json::json!({"type": "tool", "name": name}),
+        };
+        if disable_parallel_tool_use {
+            value["disable_parallel_tool_use"] = serde_json::json!(true);
+        }
+        value
+    }
+
+    /// OpenAI `tool_choice` string or object. Parallel calls are controlled
+    /// separately by the top-level `parallel_tool_calls` field.
+    pub fn to_openai(&self) -> serde_json::Value {
+        match self {
+            Self::Auto => serde_json::json!("auto"),
+            Self::Any => serde_json::json!("required"),
+            Self::None => serde_json::json!("none"),
+            Self::Tool(name) => {
+                serde_json::json!({"type": "function", "function": {"name": name}})
+            }
+        }
+    }
+
+    /// Parse an OpenAI `tool_choice` value.
+    pub fn from_openai(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::String(choice) => match choice.as_str() {
+                "auto" => Some(Self::Auto),
+                "required" => Some(Self::Any),
+                "none" => Some(Self::None),
+                _ => None,
+            },
+            serde_json::Value::Object(_) => value
+                .pointer("/function/name")
+                .and_then(serde_json::Value::as_str)
+                .map(Self::tool),
+            _ => None,
         }
     }
 }
@@ -149,10 +322,57 @@ pub struct Usage {
     pub cost: Option<f64>,
 }
 
+impl std::ops::AddAssign<&Usage> for Usage {
+    /// Sum usage across separate requests.
+    fn add_assign(&mut self, other: &Usage) {
+        fn add(left: Option<u64>, right: Option<u64>) -> Option<u64> {
+            if left.is_none() && right.is_none() {
+                None
+            } else {
+                Some(left.unwrap_or_default() + right.unwrap_or_default())
+            }
+        }
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens = add(
+            self.cache_creation_input_tokens,
+            other.cache_creation_input_tokens,
+        );
+        self.cache_read_input_tokens =
+            add(self.cache_read_input_tokens, other.cache_read_input_tokens);
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.cost = match (self.cost, other.cost) {
+            (None, None) => None,
+            (left, right) => Some(left.unwrap_or_default() + right.unwrap_or_default()),
+        };
+    }
+}
+
 impl Usage {
     pub fn total_tokens(&self) -> u64 {
         self.input_tokens + self.output_tokens
     }
+
+    /// Overlay a later usage report. Streaming providers send cumulative or
+    /// partial counts, so non-empty values replace earlier ones.
+    pub fn merge(&mut self, later: &Usage) {
+        if later.input_tokens > 0 {
+            self.input_tokens = later.input_tokens;
+        }
+        if later.output_tokens > 0 {
+            self.output_tokens = later.output_tokens;
+        }
+        if later.reasoning_tokens > 0 {
+            self.reasoning_tokens = later.reasoning_tokens;
+        }
+        self.cache_creation_input_tokens = later
+            .cache_creation_input_tokens
+            .or(self.cache_creation_input_tokens);
+        self.cache_read_input_tokens = later
+            .cache_read_input_tokens
+            .or(self.cache_read_input_tokens);
+        self.cost = later.cost.or(self.cost);
+    }
 }
 
 /// Response format specification for OpenAI-compatible APIs.
@@ -186,6 +406,47 @@ impl ResponseFormat {
     }
 }
 
+#[cfg(test)]
+mod tool_definition_tests {
+    use super::*;
+
+    #[test]
+    fn serializes_custom_and_server_tools_in_anthropic_format() {
+        let tools = vec![
+            ToolDefinition::new(
+                "lookup",
+                "Look up a record",
+                serde_json::json!({"type": "object"}),
+            ),
+            ServerTool::web_search()
+                .max_uses(3)
+                .allowed_domains(["docs.rs"])
+                .into(),
+        ];
+        let value = serde_json::to_value(&tools).expect("serialize tools");
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {
+                    "name": "lookup",
+                    "description": "Look up a record",
+                    "input_schema": { "type": "object" }
+                },
+                {
+                    "type": "web_search_20250305",
+                    "name": "web_search",
+                    "max_uses": 3,
+                    "allowed_domains": ["docs.rs"]
+                }
+            ])
+        );
+
+        let parsed: Vec<ToolDefinition> = serde_json::from_value(value).expect("parse tools");
+        assert!(!parsed[0].is_server());
+        assert!(matches!(&parsed[1], ToolDefinition::Server(tool) if tool.max_uses == Some(3)));
+    }
+}
+
 #[cfg(test)]
 mod response_format_tests {
     use super::*;
@@ -229,6 +490,9 @@ pub enum StopReason {
     EndTurn,
     ToolUse,
     MaxTokens,
+    /// A custom stop sequence was generated. Carries the matched sequence
+    /// when the provider reports it (Anthropic `stop_sequence`).
+    StopSequence(Option<String>),
     Other(String),
 }
 
@@ -238,6 +502,7 @@ impl StopReason {
             "end_turn" => Self::EndTurn,
             "tool_use" => Self::ToolUse,
             "max_tokens" => Self::MaxTokens,
+            "stop_sequence" => Self::StopSequence(None),
             other => Self::Other(other.to_string()),
         }
     }
@@ -256,6 +521,7 @@ impl StopReason {
             Self::EndTurn => "end_turn",
             Self::ToolUse => "tool_use",
             Self::MaxTokens => "max_tokens",
+            Self::StopSequence(_) => "stop_sequence",
             Self::Other(s) => s,
         }
     }
@@ -265,6 +531,7 @@ impl StopReason {
             Self::EndTurn => "stop",
             Self::ToolUse => "tool_calls",
             Self::MaxTokens => "length",
+            Self::StopSequence(_) => "stop",
             Self::Other(s) => s,
         }
     }
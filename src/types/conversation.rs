@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+
+use super::anthropic::{ContentBlock, Message, MessagesResponse};
+use super::common::ToolDefinition;
+
+/// Chat history with its system prompt and tools, kept in a shape the
+/// Anthropic Messages API accepts.
+///
+/// Appending merges consecutive same-role messages, so user text, tool
+/// results and assistant responses can be pushed in any order without
+/// breaking role alternation. Tool results are kept ahead of other blocks in
+/// a user turn. [`validate`](Self::validate) checks the tool_use/tool_result
+/// pairing; deserializing runs it too.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "ConversationData")]
+pub struct Conversation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+struct ConversationData {
+    #[serde(default)]
+    system: Option<String>,
+    #[serde(default)]
+    tools: Vec<ToolDefinition>,
+    #[serde(default)]
+    messages: Vec<Message>,
+}
+
+impl TryFrom<ConversationData> for Conversation {
+    type Error = String;
+
+    fn try_from(data: ConversationData) -> Result<Self, String> {
+        let conversation = Self {
+            system: data.system,
+            tools: data.tools,
+            messages: data.messages,
+        };
+        conversation.validate()?;
+        Ok(conversation)
+    }
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Append a message, merging it into the last one if both have the same
+    /// role. Messages without content are ignored.
+    pub fn push(&mut self, message: Message) {
+        if message.content.is_empty() {
+            return;
+        }
+        match self.messages.last_mut() {
+            Some(last) if last.role == message.role => {
+                last.content.extend(message.content);
+                if last.role == "user" {
+                    // Anthropic requires tool results before other user content.
+                    last.content
+                        .sort_by_key(|block| !matches!(block, ContentBlock::ToolResult { .. }));
+                }
+            }
+            _ => self.messages.push(message),
+        }
+    }
+
+    pub fn push_user_text(&mut self, text: impl Into<String>) {
+        self.push(Message::user_text(text));
+    }
+
+    /// Append a model response as an assistant turn.
+    pub fn push_response(&mut self, response: &MessagesResponse) {
+        self.push(Message::assistant(response.content.clone()));
+    }
+
+    /// Append `tool_result` blocks answering the last assistant turn.
+    pub fn push_tool_results(&mut self, results: Vec<ContentBlock>) {
+        self.push(Message::tool_results(results));
+    }
+
+    /// Tool calls in the last assistant turn that have no result yet.
+    pub fn pending_tool_calls(&self) -> Vec<&ContentBlock> {
+        let Some(position) = self
+            .messages
+            .iter()
+            .rposition(|message| message.role == "assistant")
+        else {
+            return Vec::new();
+        };
+        let answered = tool_result_ids(self.messages.get(position + 1));
+        self.messages[position]
+            .content
+            .iter()
+            .filter(|block| match block {
+                ContentBlock::ToolUse { id, .. } => !answered.contains(&id.as_str()),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Drop all messages, keeping the system prompt and tools.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+
+    /// Check the history against the Messages API rules: roles are `user` or
+    /// `assistant`, start with `user` and alternate; every `tool_use` is
+    /// answered by a `tool_result` in the next message (only the last turn may
+    /// have calls pending) and every `tool_result` answers a call from the
+    /// turn before it.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(first) = self.messages.first()
+            && first.role != "user"
+        {
+            return Err(format!(
+                "conversation must start with a user message, not {}",
+                first.role
+            ));
+        }
+        for (index, message) in self.messages.iter().enumerate() {
+            if message.role != "user" && message.role != "assistant" {
+                return Err(format!("message {index}: unknown role {}", message.role));
+            }
+            if index > 0 && self.messages[index - 1].role == message.role {
+                return Err(format!(
+                    "message {index}: consecutive {} messages",
+                    message.role
+                ));
+            }
+            let results = tool_result_ids(Some(message));
+            if message.role == "assistant" {
+                if !results.is_empty() {
+                    return Err(format!("message {index}: tool_result in an assistant turn"));
+                }
+                let answered = tool_result_ids(self.messages.get(index + 1));
+                for block in &message.content {
+                    if let ContentBlock::ToolUse { id, .. } = block
+                        && !answered.contains(&id.as_str())
+                        && index + 1 < self.messages.len()
+                    {
+                        return Err(format!("message {index}: tool_use {id} has no tool_result"));
+                    }
+                }
+                continue;
+            }
+            let calls = index
+                .checked_sub(1)
+                .map(|previous| tool_use_ids(&self.messages[previous]))
+                .unwrap_or_default();
+            for (position, id) in results.iter().enumerate() {
+                if !calls.contains(id) {
+                    return Err(format!(
+                        "message {index}: tool_result for unknown tool_use {id}"
+                    ));
+                }
+                if results[..position].contains(id) {
+                    return Err(format!("message {index}: duplicate tool_result for {id}"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tool_use_ids(message: &Message) -> Vec<&str> {
+    message
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn tool_result_ids(message: Option<&Message>) -> Vec<&str> {
+    message
+        .into_iter()
+        .flat_map(|message| &message.content)
+        .filter_map(|block| match block {
+            ContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merges_turns_and_tracks_tool_calls() {
+        let mut conversation = Conversation::new().with_system("Be brief.");
+        conversation.push_user_text("Weather in Oslo?");
+        conversation.push(Message::assistant(vec![
+            ContentBlock::text("Checking."),
+            ContentBlock::tool_use("toolu_1", "weather", json!({ "city": "Oslo" })),
+            ContentBlock::tool_use("toolu_2", "time", json!({ "city": "Oslo" })),
+        ]));
+        assert_eq!(conversation.pending_tool_calls().len(), 2);
+
+        conversation.push_user_text("Also in Bergen, please.");
+        conversation.push_tool_results(vec![ContentBlock::tool_result("toolu_1", "Sunny", false)]);
+        assert_eq!(conversation.messages().len(), 3);
+        assert!(matches!(
+            conversation.messages()[2].content[0],
+            ContentBlock::ToolResult { .. }
+        ));
+        assert_eq!(conversation.pending_tool_calls().len(), 1);
+        assert_eq!(
+            conversation.validate().unwrap_err(),
+            "message 1: tool_use toolu_2 has no tool_result"
+        );
+
+        conversation.push_tool_results(vec![ContentBlock::tool_result("toolu_2", "12:00", false)]);
+        conversation.validate().unwrap();
+        assert!(conversation.pending_tool_calls().is_empty());
+
+        let json = serde_json::to_value(&conversation).unwrap();
+        assert_eq!(json["system"], "Be brief.");
+        let restored: Conversation = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.messages().len(), 3);
+    }
+
+    #[test]
+    fn rejects_invalid_persisted_history() {
+        let orphan = json!({
+            "messages": [
+                { "role": "user", "content": [{ "type": "text", "text": "hi" }] },
+                { "role": "assistant", "content": [{ "type": "text", "text": "hello" }] },
+                {
+                    "role": "user",
+                    "content": [{ "type": "tool_result", "tool_use_id": "toolu_9", "content": "x" }]
+                }
+            ]
+        });
+        let error = serde_json::from_value::<Conversation>(orphan).unwrap_err();
+        assert!(error.to_string().contains("unknown tool_use toolu_9"));
+
+        let assistant_first = json!({
+            "messages": [{ "role": "assistant", "content": [{ "type": "text", "text": "hi" }] }]
+        });
+        assert!(serde_json::from_value::<Conversation>(assistant_first).is_err());
+    }
+}
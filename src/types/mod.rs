@@ -1,3 +1,5 @@
 pub mod anthropic;
 pub mod common;
+pub mod conversation;
 pub mod openai;
+pub mod stream;
@@ -14,11 +14,28 @@ pub struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_format: Option<ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    /// Reasoning effort for reasoning models (`low`, `medium`, `high`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
 }
 
 /// OpenAI chat message (for requests).
@@ -26,7 +43,7 @@ pub struct ChatRequest {
 pub struct ChatMessage {
     pub role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub content: Option<String>,
+    pub content: Option<ChatContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCallOut>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,7 +54,7 @@ impl ChatMessage {
     pub fn system(content: impl Into<String>) -> Self {
         Self {
             role: "system".to_string(),
-            content: Some(content.into()),
+            content: Some(ChatContent::Text(content.into())),
             tool_calls: None,
             tool_call_id: None,
         }
@@ -46,7 +63,17 @@ impl ChatMessage {
     pub fn user(content: impl Into<String>) -> Self {
         Self {
             role: "user".to_string(),
-            content: Some(content.into()),
+            content: Some(ChatContent::Text(content.into())),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    /// User message with multimodal content parts.
+    pub fn user_parts(parts: Vec<ContentPart>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: Some(ChatContent::Parts(parts)),
             tool_calls: None,
             tool_call_id: None,
         }
@@ -55,7 +82,7 @@ impl ChatMessage {
     pub fn assistant_text(content: impl Into<String>) -> Self {
         Self {
             role: "assistant".to_string(),
-            content: Some(content.into()),
+            content: Some(ChatContent::Text(content.into())),
             tool_calls: None,
             tool_call_id: None,
         }
@@ -64,13 +91,41 @@ impl ChatMessage {
     pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
         Self {
             role: "tool".to_string(),
-            content: Some(content.into()),
+            content: Some(ChatContent::Text(content.into())),
             tool_calls: None,
             tool_call_id: Some(tool_call_id.into()),
         }
     }
 }
 
+/// Message content: a plain string or an array of typed parts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChatContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// Content part of a multimodal chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrlData },
+    File { file: FileData },
+}
+
+/// Inline file attachment (`file` content part).
+///
+/// `file_data` is a `data:` URL; OpenRouter additionally accepts plain URLs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<String>,
+}
+
 /// OpenAI tool definition.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
@@ -149,10 +204,33 @@ pub struct ResponseMessage {
     pub content: Option<String>,
     #[serde(default, alias = "reasoning", skip_serializing_if = "Option::is_none")]
     pub reasoning_content: Option<String>,
+    /// Structured reasoning metadata (OpenRouter `reasoning_details`), used to
+    /// carry thinking signatures and redacted thinking across formats.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_details: Option<Vec<ReasoningDetail>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ResponseToolCall>>,
 }
 
+/// One entry of `reasoning_details`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ReasoningDetail {
+    /// Plain reasoning text with an optional provider signature.
+    #[serde(rename = "reasoning.text")]
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+    /// Opaque encrypted reasoning (Anthropic `redacted_thinking`).
+    #[serde(rename = "reasoning.encrypted")]
+    Encrypted { data: String },
+    /// Any other entry, such as `reasoning.summary`.
+    #[serde(other)]
+    Other,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseToolCall {
     pub id: String,
@@ -231,9 +309,18 @@ pub struct InboundChatRequest {
     pub stream: Option<bool>,
     #[serde(default)]
     pub top_p: Option<f32>,
+    /// Stop sequence(s): a string or an array of strings.
+    #[serde(default)]
+    pub stop: Option<serde_json::Value>,
+    #[serde(default)]
+    pub user: Option<String>,
     #[serde(default)]
     pub tools: Option<Vec<serde_json::Value>>,
     #[serde(default)]
+    pub tool_choice: Option<serde_json::Value>,
+    #[serde(default)]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(default)]
     pub reasoning_effort: Option<String>,
 }
 
@@ -263,9 +350,11 @@ pub enum InboundContentPart {
     Text { text: String },
     #[serde(rename = "image_url")]
     ImageUrl { image_url: ImageUrlData },
+    #[serde(rename = "file")]
+    File { file: FileData },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageUrlData {
     pub url: String,
 }
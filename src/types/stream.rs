@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use super::common::{StopReason, Usage};
+
+/// Provider-neutral streaming event.
+///
+/// Mirrors the Anthropic block lifecycle: `MessageStart`, then for each
+/// content block `BlockStart`, deltas and `BlockStop`, then `Usage` and
+/// `Done`. OpenAI chunk streams are decoded into the same sequence by
+/// [`crate::convert::stream::StreamDecoder`]. `index` is the position of the
+/// block in the final message content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    MessageStart {
+        id: Option<String>,
+        model: Option<String>,
+    },
+    BlockStart {
+        index: usize,
+        kind: BlockKind,
+    },
+    TextDelta {
+        index: usize,
+        text: String,
+    },
+    ThinkingDelta {
+        index: usize,
+        text: String,
+    },
+    /// Signature for the thinking block at `index`; replay it unchanged.
+    ThinkingSignature {
+        index: usize,
+        signature: String,
+    },
+    /// Raw fragment of a tool call's JSON input.
+    ToolCallDelta {
+        index: usize,
+        arguments: String,
+    },
+    BlockStop {
+        index: usize,
+    },
+    /// Best-effort parse of a tool call's arguments so far. Derived from
+    /// `ToolCallDelta` by the client's tool call tracker; decoders never
+    /// produce it.
+    ToolCallPartial {
+        index: usize,
+        value: serde_json::Value,
+    },
+    /// Complete tool input, validated as a JSON object when the tool call
+    /// block stops. Derived like `ToolCallPartial`.
+    ToolCallComplete {
+        index: usize,
+        input: serde_json::Value,
+    },
+    /// Token usage so far. Later events carry cumulative counts.
+    Usage {
+        usage: Usage,
+    },
+    /// Keep-alive; carries no data.
+    Ping,
+    /// Error reported by the provider inside a successful (HTTP 200) stream,
+    /// e.g. Anthropic `overloaded_error`.
+    Error {
+        error_type: String,
+        message: String,
+    },
+    Done {
+        stop_reason: Option<StopReason>,
+    },
+}
+
+/// Kind of content block opened by [`StreamEvent::BlockStart`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockKind {
+    Text,
+    Thinking,
+    /// Opaque redacted thinking; replay it unchanged as
+    /// `ContentBlock::RedactedThinking` on the next turn.
+    RedactedThinking {
+        data: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+    },
+    ServerToolUse {
+        id: String,
+        name: String,
+    },
+    /// Any other block (e.g. `web_search_tool_result`), delivered whole.
+    Other {
+        block: serde_json::Value,
+    },
+}